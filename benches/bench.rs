@@ -5,9 +5,28 @@ extern crate test;
 use fnv::FnvHashMap;
 use tantivity_term_map::map::TermHashMap;
 
+fn get_or_insert_prefer_get<'a, T, F>(
+    map: *mut FnvHashMap<String, T>,
+    key: &str,
+    mut constructor: F,
+) -> &'a mut T
+where
+    F: FnMut() -> T,
+{
+    unsafe {
+        if let Some(e) = (*map).get_mut(key) {
+            return e;
+        }
+
+        (*map).insert(key.to_string(), constructor());
+        (*map).get_mut(key).unwrap()
+    }
+}
+
 #[cfg(test)]
 mod tests {
 
+    #[allow(dead_code)]
     #[derive(Debug, Default, Clone, Copy)]
     struct MoreMetaData {
         counter1: usize,
@@ -18,6 +37,10 @@ mod tests {
     }
 
     use super::*;
+    use inohashmap::hasher::{Murmur2, WyHash, XxHash32};
+    use inohashmap::CompositeStringHashMap;
+    use inohashmap::DenseStringHashMap;
+    use inohashmap::InlineStringHashMap;
     use inohashmap::StringHashMap;
 
     use std::io::Read;
@@ -94,6 +117,43 @@ mod tests {
         });
     }
     #[bench]
+    fn bench_dense_hasmap_full_large_struct(b: &mut Bencher) {
+        let contents = get_test_string_full();
+
+        b.iter(|| {
+            let mut map = DenseStringHashMap::<MoreMetaData>::with_power_of_two_size(10);
+            for text in contents.split_whitespace() {
+                let value = map.get_or_create(text, MoreMetaData::default());
+                value.counter1 += 1;
+            }
+        });
+    }
+    #[bench]
+    fn bench_bigrams_composite(b: &mut Bencher) {
+        let contents = get_test_string_full();
+        let tokens = contents.split_whitespace().collect::<Vec<_>>();
+
+        b.iter(|| {
+            let mut map = CompositeStringHashMap::<u32>::with_power_of_two_size(10);
+            for bigram in tokens.windows(2) {
+                *map.get_or_create_parts(bigram, 0) += 1;
+            }
+        });
+    }
+    #[bench]
+    fn bench_bigrams_concat(b: &mut Bencher) {
+        let contents = get_test_string_full();
+        let tokens = contents.split_whitespace().collect::<Vec<_>>();
+
+        b.iter(|| {
+            let mut map = StringHashMap::<u32>::with_power_of_two_size(10);
+            for bigram in tokens.windows(2) {
+                let key = bigram.join(" ");
+                *map.get_or_create(&key, 0) += 1;
+            }
+        });
+    }
+    #[bench]
     fn bench_hasmap(b: &mut Bencher) {
         let contents = get_test_string();
 
@@ -118,6 +178,73 @@ mod tests {
         });
     }
     #[bench]
+    fn bench_hasmap_full_murmur2(b: &mut Bencher) {
+        let contents = get_test_string_full();
+
+        b.iter(|| {
+            let mut map =
+                StringHashMap::<u32, _, _>::with_power_of_two_size_and_hasher(10, Murmur2);
+            for text in contents.split_whitespace() {
+                let value = map.get_or_create(text, 0);
+                *value += 1;
+            }
+        });
+    }
+    #[bench]
+    fn bench_hasmap_full_xxhash32(b: &mut Bencher) {
+        let contents = get_test_string_full();
+
+        b.iter(|| {
+            let mut map =
+                StringHashMap::<u32, _, _>::with_power_of_two_size_and_hasher(10, XxHash32);
+            for text in contents.split_whitespace() {
+                let value = map.get_or_create(text, 0);
+                *value += 1;
+            }
+        });
+    }
+    #[bench]
+    fn bench_hasmap_full_wyhash(b: &mut Bencher) {
+        let contents = get_test_string_full();
+
+        b.iter(|| {
+            let mut map = StringHashMap::<u32, _, _>::with_power_of_two_size_and_hasher(10, WyHash);
+            for text in contents.split_whitespace() {
+                let value = map.get_or_create(text, 0);
+                *value += 1;
+            }
+        });
+    }
+    #[bench]
+    fn bench_inline_hasmap_full(b: &mut Bencher) {
+        let contents = get_test_string_full();
+
+        b.iter(|| {
+            let mut map = InlineStringHashMap::<u32>::with_power_of_two_size_and_inline_keys(10);
+            for text in contents.split_whitespace() {
+                let value = map.get_or_create(text, 0);
+                *value += 1;
+            }
+        });
+    }
+    #[bench]
+    fn bench_inline_hasmap_full_get(b: &mut Bencher) {
+        let contents = get_test_string_full();
+
+        let mut map = InlineStringHashMap::<u32>::with_power_of_two_size_and_inline_keys(10);
+        for text in contents.split_whitespace() {
+            let value = map.get_or_create(text, 0);
+            *value += 1;
+        }
+
+        b.iter(|| {
+            for text in contents.split_whitespace() {
+                let value = map.get_or_create(text, 0);
+                *value += 1;
+            }
+        });
+    }
+    #[bench]
     fn bench_hasmap_full_get(b: &mut Bencher) {
         let contents = get_test_string_full();
 
@@ -135,6 +262,48 @@ mod tests {
         });
     }
 
+    #[bench]
+    fn bench_hasmap_full_tokens(b: &mut Bencher) {
+        let contents = get_test_string_full();
+        let tokens = contents.split_whitespace().collect::<Vec<_>>();
+
+        b.iter(|| {
+            let mut map = StringHashMap::<u32>::with_power_of_two_size(10);
+            for text in tokens.iter() {
+                let value = map.get_or_create(*text, 0);
+                *value += 1;
+            }
+        });
+    }
+    #[bench]
+    fn bench_hasmap_full_batch(b: &mut Bencher) {
+        let contents = get_test_string_full();
+        let tokens = contents.split_whitespace().collect::<Vec<_>>();
+
+        b.iter(|| {
+            let mut map = StringHashMap::<u32>::with_power_of_two_size(10);
+            map.get_or_create_batch(&tokens, || 0, |_, value| *value += 1);
+        });
+    }
+    #[bench]
+    fn bench_hasmap_full_get_tokens(b: &mut Bencher) {
+        let contents = get_test_string_full();
+        let tokens = contents.split_whitespace().collect::<Vec<_>>();
+        let mut map = StringHashMap::<u32>::with_power_of_two_size(10);
+        map.get_or_create_batch(&tokens, || 0, |_, value| *value += 1);
+
+        b.iter(|| tokens.iter().map(|text| map.get(*text)).collect::<Vec<_>>());
+    }
+    #[bench]
+    fn bench_hasmap_full_get_many(b: &mut Bencher) {
+        let contents = get_test_string_full();
+        let tokens = contents.split_whitespace().collect::<Vec<_>>();
+        let mut map = StringHashMap::<u32>::with_power_of_two_size(10);
+        map.get_or_create_batch(&tokens, || 0, |_, value| *value += 1);
+
+        b.iter(|| map.get_many(&tokens));
+    }
+
     #[bench]
     fn bench_tant_termmap_full_get(b: &mut Bencher) {
         let contents = get_test_string_full();
@@ -191,21 +360,3 @@ mod tests {
         });
     }
 }
-
-fn get_or_insert_prefer_get<'a, T, F>(
-    map: *mut FnvHashMap<String, T>,
-    key: &str,
-    mut constructor: F,
-) -> &'a mut T
-where
-    F: FnMut() -> T,
-{
-    unsafe {
-        if let Some(e) = (*map).get_mut(key) {
-            return e;
-        }
-
-        (*map).insert(key.to_string(), constructor());
-        (*map).get_mut(key).unwrap()
-    }
-}
@@ -0,0 +1,456 @@
+/*! Group by aggregation with a `StringHashMap`.
+
+`StringAggMap<A>` keeps one `Aggregator` per key in the table of a `StringHashMap`. `update` feeds
+an input to the aggregator of a key, and `merge` combines the aggregators of two maps, e.g. maps
+built by separate threads on parts of the data.
+
+The built in aggregators are `Count`, `Sum`, `MinMax`, `Mean` and `HllDistinct`. Tuples of two
+aggregators update both with the same input.
+
+# Examples
+```
+use inohashmap::agg::{Mean, MinMax};
+use inohashmap::StringAggMap;
+let mut prices = StringAggMap::<(MinMax<u32>, Mean)>::new();
+for (fruit, price) in [("apple", 3), ("pear", 5), ("apple", 7)].iter() {
+    prices.update(fruit, (*price, f64::from(*price)));
+}
+assert_eq!(prices.result("apple"), Some((Some((3, 7)), Some(5.0))));
+assert_eq!(prices.result("banana"), None);
+```
+
+*/
+
+use crate::StringHashMap;
+use core::fmt::Debug;
+use core::ops::AddAssign;
+
+/// Accumulates the inputs of a key into a single result.
+pub trait Aggregator: Default + Clone + Debug {
+    type Input;
+    type Output;
+
+    /// Adds `input` to the aggregate.
+    fn update(&mut self, input: Self::Input);
+
+    /// Adds the inputs of `other`, as if they were passed to `update` of `self`.
+    fn merge(&mut self, other: &Self);
+
+    fn result(&self) -> Self::Output;
+}
+
+#[derive(Debug)]
+pub struct StringAggMap<A: Aggregator> {
+    map: StringHashMap<A>,
+}
+
+impl<A: Aggregator> Default for StringAggMap<A> {
+    fn default() -> Self {
+        StringAggMap::with_power_of_two_size(10)
+    }
+}
+
+impl<A: Aggregator> StringAggMap<A> {
+    #[inline]
+    pub fn with_power_of_two_size(power_of_two: usize) -> Self {
+        StringAggMap {
+            map: StringHashMap::with_power_of_two_size(power_of_two),
+        }
+    }
+    #[inline]
+    pub fn new() -> Self {
+        Self::with_power_of_two_size(10)
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    #[inline]
+    pub fn shrink_to_fit(&mut self) {
+        self.map.shrink_to_fit();
+    }
+
+    /// Returns the map with the aggregators.
+    #[inline]
+    pub fn map(&self) -> &StringHashMap<A> {
+        &self.map
+    }
+
+    /// Adds `input` to the aggregator of `el`, which starts as `A::default()`.
+    #[inline]
+    pub fn update(&mut self, el: &str, input: A::Input) {
+        self.map.entry(el).or_default().update(input);
+    }
+
+    /// Merges the aggregators of `other` into the ones of the same keys.
+    pub fn merge(&mut self, other: &StringAggMap<A>) {
+        for (key, aggregator) in other.map.iter() {
+            self.map.entry(key).or_default().merge(aggregator);
+        }
+    }
+
+    #[inline]
+    pub fn get(&self, el: &str) -> Option<&A> {
+        self.map.get(el)
+    }
+
+    /// Returns the result of the aggregator of `el`.
+    #[inline]
+    pub fn result(&self, el: &str) -> Option<A::Output> {
+        self.map.get(el).map(A::result)
+    }
+
+    /// Returns the keys and aggregators, in table order.
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &A)> {
+        self.map.iter()
+    }
+
+    /// Returns the keys and results, in table order.
+    #[inline]
+    pub fn results(&self) -> impl Iterator<Item = (&str, A::Output)> {
+        self.map
+            .iter()
+            .map(|(key, aggregator)| (key, aggregator.result()))
+    }
+}
+
+impl<A: Aggregator, B: Aggregator> Aggregator for (A, B) {
+    type Input = (A::Input, B::Input);
+    type Output = (A::Output, B::Output);
+
+    #[inline]
+    fn update(&mut self, input: Self::Input) {
+        self.0.update(input.0);
+        self.1.update(input.1);
+    }
+
+    #[inline]
+    fn merge(&mut self, other: &Self) {
+        self.0.merge(&other.0);
+        self.1.merge(&other.1);
+    }
+
+    #[inline]
+    fn result(&self) -> Self::Output {
+        (self.0.result(), self.1.result())
+    }
+}
+
+/// Counts the updates.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Count(pub u64);
+
+impl Aggregator for Count {
+    type Input = ();
+    type Output = u64;
+
+    #[inline]
+    fn update(&mut self, _input: ()) {
+        self.0 += 1;
+    }
+
+    #[inline]
+    fn merge(&mut self, other: &Self) {
+        self.0 += other.0;
+    }
+
+    #[inline]
+    fn result(&self) -> u64 {
+        self.0
+    }
+}
+
+/// Sums the inputs.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Sum<T>(pub T);
+
+impl<T: AddAssign + Copy + Default + Debug> Aggregator for Sum<T> {
+    type Input = T;
+    type Output = T;
+
+    #[inline]
+    fn update(&mut self, input: T) {
+        self.0 += input;
+    }
+
+    #[inline]
+    fn merge(&mut self, other: &Self) {
+        self.0 += other.0;
+    }
+
+    #[inline]
+    fn result(&self) -> T {
+        self.0
+    }
+}
+
+/// Keeps the smallest and largest input, `None` before the first update.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MinMax<T>(pub Option<(T, T)>);
+
+impl<T> Default for MinMax<T> {
+    fn default() -> Self {
+        MinMax(None)
+    }
+}
+
+impl<T: PartialOrd + Copy + Debug> Aggregator for MinMax<T> {
+    type Input = T;
+    type Output = Option<(T, T)>;
+
+    #[inline]
+    fn update(&mut self, input: T) {
+        self.merge(&MinMax(Some((input, input))));
+    }
+
+    #[inline]
+    fn merge(&mut self, other: &Self) {
+        if let Some((other_min, other_max)) = other.0 {
+            self.0 = Some(match self.0 {
+                None => (other_min, other_max),
+                Some((min, max)) => (
+                    if other_min < min { other_min } else { min },
+                    if other_max > max { other_max } else { max },
+                ),
+            });
+        }
+    }
+
+    #[inline]
+    fn result(&self) -> Option<(T, T)> {
+        self.0
+    }
+}
+
+/// Arithmetic mean of the inputs, `None` before the first update.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Mean {
+    pub sum: f64,
+    pub count: u64,
+}
+
+impl Aggregator for Mean {
+    type Input = f64;
+    type Output = Option<f64>;
+
+    #[inline]
+    fn update(&mut self, input: f64) {
+        self.sum += input;
+        self.count += 1;
+    }
+
+    #[inline]
+    fn merge(&mut self, other: &Self) {
+        self.sum += other.sum;
+        self.count += other.count;
+    }
+
+    #[inline]
+    fn result(&self) -> Option<f64> {
+        if self.count == 0 {
+            None
+        } else {
+            Some(self.sum / self.count as f64)
+        }
+    }
+}
+
+/// Number of registers of `HllDistinct`, as power of two.
+const HLL_PRECISION: u32 = 8;
+const HLL_REGISTERS: usize = 1 << HLL_PRECISION;
+
+/// Estimates the number of distinct inputs with HyperLogLog.
+///
+/// The 256 registers take 256 bytes per key, the standard error of the estimate is about 6.5%.
+/// Inputs are `u64` ids, which are mixed before they are counted. To count distinct strings,
+/// pass a 64 bit hash of them, e.g. of `StringHasher::hash64`.
+#[derive(Clone)]
+pub struct HllDistinct {
+    registers: [u8; HLL_REGISTERS],
+}
+
+impl Default for HllDistinct {
+    fn default() -> Self {
+        HllDistinct {
+            registers: [0; HLL_REGISTERS],
+        }
+    }
+}
+
+impl Debug for HllDistinct {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("HllDistinct")
+            .field("estimate", &self.result())
+            .finish()
+    }
+}
+
+impl Aggregator for HllDistinct {
+    type Input = u64;
+    type Output = u64;
+
+    #[inline]
+    fn update(&mut self, input: u64) {
+        let hash = mix64(input);
+        let register = (hash >> (64 - HLL_PRECISION)) as usize;
+        // position of the first set bit of the remaining bits, the marker bit bounds the rank
+        let rank = ((hash << HLL_PRECISION) | (1 << (HLL_PRECISION - 1))).leading_zeros() + 1;
+        let register = &mut self.registers[register];
+        *register = (*register).max(rank as u8);
+    }
+
+    #[inline]
+    fn merge(&mut self, other: &Self) {
+        for (register, other) in self.registers.iter_mut().zip(other.registers.iter()) {
+            *register = (*register).max(*other);
+        }
+    }
+
+    fn result(&self) -> u64 {
+        let m = HLL_REGISTERS as f64;
+        let mut sum = 0.0;
+        let mut zeros = 0;
+        for &register in self.registers.iter() {
+            // 2^-register, built from the exponent bits
+            sum += f64::from_bits((1023 - u64::from(register)) << 52);
+            if register == 0 {
+                zeros += 1;
+            }
+        }
+        let alpha = 0.7213 / (1.0 + 1.079 / m);
+        let estimate = alpha * m * m / sum;
+        if estimate <= 2.5 * m && zeros > 0 {
+            // linear counting is more accurate for small cardinalities
+            (m * ln(m / f64::from(zeros)) + 0.5) as u64
+        } else {
+            (estimate + 0.5) as u64
+        }
+    }
+}
+
+/// Finalizer of murmur3, spreads the bits of ids which differ only in a few bits.
+#[inline]
+fn mix64(mut value: u64) -> u64 {
+    value ^= value >> 33;
+    value = value.wrapping_mul(0xff51_afd7_ed55_8ccd);
+    value ^= value >> 33;
+    value = value.wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+    value ^ (value >> 33)
+}
+
+/// Natural logarithm of a positive, normal `x`. `f64::ln` is not available without std.
+fn ln(x: f64) -> f64 {
+    // x = mantissa * 2^exponent with mantissa in [1, 2)
+    let bits = x.to_bits();
+    let exponent = ((bits >> 52) & 0x7ff) as i64 - 1023;
+    let mantissa = f64::from_bits((bits & ((1 << 52) - 1)) | (1023 << 52));
+    // ln(mantissa) = 2 * atanh(s), with s <= 1/3 the series converges quickly
+    let s = (mantissa - 1.0) / (mantissa + 1.0);
+    let s2 = s * s;
+    let mut term = s;
+    let mut series = 0.0;
+    for k in 0..20 {
+        series += term / f64::from(2 * k + 1);
+        term *= s2;
+    }
+    exponent as f64 * core::f64::consts::LN_2 + 2.0 * series
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::test_words;
+    use std::vec::Vec;
+
+    #[test]
+    fn word_stats() {
+        let contents = test_words();
+        // line numbers per word
+        let mut map = StringAggMap::<((Count, Sum<u64>), (MinMax<usize>, Mean))>::new();
+        let mut reference: std::collections::HashMap<&str, Vec<usize>> = Default::default();
+        for (line_number, line) in contents.lines().enumerate() {
+            for text in line.split_whitespace() {
+                map.update(
+                    text,
+                    (((), line_number as u64), (line_number, line_number as f64)),
+                );
+                reference.entry(text).or_default().push(line_number);
+            }
+        }
+        assert_eq!(map.len(), reference.len());
+        for (key, lines) in reference.iter() {
+            let ((count, sum), (min_max, mean)) = map.result(key).unwrap();
+            let expected_sum = lines.iter().sum::<usize>();
+            assert_eq!(count, lines.len() as u64);
+            assert_eq!(sum, expected_sum as u64);
+            assert_eq!(min_max, Some((lines[0], *lines.last().unwrap())));
+            let expected_mean = expected_sum as f64 / lines.len() as f64;
+            assert!((mean.unwrap() - expected_mean).abs() < 1e-6);
+        }
+        assert_eq!(map.results().count(), reference.len());
+        assert_eq!(map.result("not in the map"), None);
+    }
+
+    #[test]
+    fn merge_is_same_as_single_map() {
+        let mut single = StringAggMap::<(Count, MinMax<i32>)>::new();
+        let mut parts = (0..3)
+            .map(|_| StringAggMap::<(Count, MinMax<i32>)>::with_power_of_two_size(1))
+            .collect::<Vec<_>>();
+        for i in 0..1000i32 {
+            let key = format!("key{}", i % 37);
+            let value = (i * 7919) % 1000 - 500;
+            single.update(&key, ((), value));
+            parts[(i % 3) as usize].update(&key, ((), value));
+        }
+        let mut merged = StringAggMap::new();
+        for part in parts.iter() {
+            merged.merge(part);
+        }
+        assert_eq!(merged.len(), single.len());
+        for (key, result) in single.results() {
+            assert_eq!(merged.result(key), Some(result));
+        }
+        assert_eq!(Mean::default().result(), None);
+        assert_eq!(MinMax::<u8>::default().result(), None);
+    }
+
+    #[test]
+    fn hll_estimate() {
+        for &cardinality in [0u64, 1, 10, 100, 1000, 100_000].iter() {
+            let mut hll = HllDistinct::default();
+            let mut halves = (HllDistinct::default(), HllDistinct::default());
+            for i in 0..cardinality {
+                // every id twice
+                hll.update(i);
+                hll.update(i);
+                if i % 2 == 0 {
+                    halves.0.update(i);
+                } else {
+                    halves.1.update(i);
+                }
+            }
+            let estimate = hll.result() as f64;
+            let error = (estimate - cardinality as f64).abs() / (cardinality as f64).max(1.0);
+            // 3 standard errors
+            assert!(error < 0.2, "{} estimated as {}", cardinality, estimate);
+            halves.0.merge(&halves.1);
+            assert_eq!(halves.0.result(), hll.result());
+        }
+    }
+
+    #[test]
+    fn ln_is_accurate() {
+        for &x in [1.0f64, 1.5, 2.0, 256.0, 256.0 / 3.0, 1e-3, 12345.678].iter() {
+            assert!((ln(x) - x.ln()).abs() < 1e-12, "ln({})", x);
+        }
+    }
+}
@@ -0,0 +1,137 @@
+//! Batched lookups, which hide the cache misses into the table and the string data with software
+//! prefetching.
+
+use crate::hasher::StringHasher;
+use crate::pool::private::KeySlot;
+use crate::{Allocator, HashedKey, KeyStorage, StringHashMap};
+use alloc::vec::Vec;
+use core::fmt::Debug;
+
+/// Number of keys which are hashed and prefetched ahead.
+const WINDOW: usize = 16;
+
+#[inline(always)]
+fn prefetch<P>(ptr: *const P) {
+    #[cfg(target_arch = "x86_64")]
+    unsafe {
+        use core::arch::x86_64::{_mm_prefetch, _MM_HINT_T0};
+        _mm_prefetch(ptr as *const i8, _MM_HINT_T0);
+    }
+    #[cfg(not(target_arch = "x86_64"))]
+    let _ = ptr;
+}
+
+impl<T: Default + Clone + Debug, A: Allocator + Clone, H: StringHasher, S: KeyStorage>
+    StringHashMap<T, A, H, S>
+{
+    /// Calls `get_or_create` for all `keys`, and `callback` with the position of the key in
+    /// `keys` and its value. `init` creates the value for keys which are not in the map yet.
+    ///
+    /// Keys are hashed and their buckets prefetched in windows, which is faster than calling
+    /// `get_or_create` in a loop for maps which don't fit into the cache.
+    ///
+    /// # Examples
+    /// ```
+    /// use inohashmap::StringHashMap;
+    /// let mut hashmap = StringHashMap::<u32>::new();
+    /// let tokens = "a b a c a".split_whitespace().collect::<Vec<_>>();
+    /// hashmap.get_or_create_batch(&tokens, || 0, |_, value| *value += 1);
+    /// assert_eq!(hashmap.get("a"), Some(&3));
+    /// ```
+    pub fn get_or_create_batch<I, F>(&mut self, keys: &[&str], mut init: I, mut callback: F)
+    where
+        I: FnMut() -> T,
+        F: FnMut(usize, &mut T),
+    {
+        let mut window = [HashedKey::from_hash("", 0); WINDOW];
+        for (chunk_pos, chunk) in keys.chunks(WINDOW).enumerate() {
+            let window = self.prefetch_window(chunk, &mut window);
+            let num_reseeds = self.num_reseeds;
+            for (pos, key) in window.iter().enumerate() {
+                // the hashes of the window are stale after the hasher has been re-seeded
+                let key = if self.num_reseeds == num_reseeds {
+                    *key
+                } else {
+                    self.hash_key(key.key())
+                };
+                let entry = self.get_or_create_entry_with(key, &mut init);
+                callback(chunk_pos * WINDOW + pos, &mut entry.value);
+            }
+        }
+    }
+
+    /// Returns the values of all `keys`, in the same order.
+    ///
+    /// Keys are hashed and their buckets prefetched in windows, which is faster than calling
+    /// `get` in a loop for maps which don't fit into the cache.
+    pub fn get_many(&self, keys: &[&str]) -> Vec<Option<&T>> {
+        let mut values = Vec::with_capacity(keys.len());
+        let mut window = [HashedKey::from_hash("", 0); WINDOW];
+        for chunk in keys.chunks(WINDOW) {
+            for key in self.prefetch_window(chunk, &mut window).iter() {
+                values.push(self.find_entry(*key).map(|entry| &entry.value));
+            }
+        }
+        values
+    }
+
+    /// Hashes `keys` into `window`, and prefetches their first bucket and the key stored there.
+    #[inline]
+    fn prefetch_window<'w, 'k>(
+        &self,
+        keys: &[&'k str],
+        window: &'w mut [HashedKey<'k, H>; WINDOW],
+    ) -> &'w [HashedKey<'k, H>] {
+        let mut buckets = [0; WINDOW];
+        for ((key, hashed), bucket) in keys.iter().zip(window.iter_mut()).zip(buckets.iter_mut()) {
+            *hashed = self.hash_key(key);
+            *bucket = self.get_probe(hashed.hash64()).next_probe();
+            prefetch(self.get_entry(*bucket));
+        }
+        // the buckets of the first keys have arrived in the meantime
+        for bucket in buckets[..keys.len()].iter() {
+            let pointer = &self.get_entry(*bucket).pointer;
+            if !pointer.is_null() {
+                prefetch(self.string_data.key_ptr(pointer));
+            }
+        }
+        &window[..keys.len()]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::{assert_same_values, count_words, test_words};
+    use crate::StringHashMap;
+    use std::vec::Vec;
+
+    #[test]
+    fn batch_same_as_single() {
+        let contents = test_words();
+        let tokens = contents.split_whitespace().collect::<Vec<_>>();
+
+        let mut batch = StringHashMap::<u32>::with_power_of_two_size(1);
+        let mut positions = Vec::new();
+        batch.get_or_create_batch(
+            &tokens,
+            || 0,
+            |pos, value| {
+                positions.push(pos);
+                *value += 1;
+            },
+        );
+        assert_eq!(positions, (0..tokens.len()).collect::<Vec<_>>());
+
+        let single = count_words(&contents);
+        assert_same_values(&single, batch.len(), |key| batch.get(key).copied());
+
+        let mut lookups = tokens.clone();
+        lookups.push("not in the map");
+        let values = batch.get_many(&lookups);
+        assert_eq!(values.len(), lookups.len());
+        for (key, value) in lookups.iter().zip(values) {
+            assert_eq!(value, single.get(*key));
+        }
+        assert_eq!(batch.get_many(&[]), Vec::<Option<&u32>>::new());
+    }
+}
@@ -0,0 +1,357 @@
+/*! Hashmap with a byte slice as value, stored in the string data right after the key.
+
+`StringBlobMap` avoids an allocation per key, which a `StringHashMap<Vec<u8>>` would need. Each
+entry in the string data is the varint length prefixed key followed by the varint length
+prefixed value.
+
+Values can be changed with `append` and `replace`. A value is changed in place if it belongs to
+the last inserted key or keeps its length, otherwise key and value are copied to the end of the
+string data. The old copy stays behind as unused bytes, see `unused_bytes`.
+
+# Examples
+```
+use inohashmap::StringBlobMap;
+let mut blobs = StringBlobMap::new();
+blobs.get_or_create_with("blub", |buf| buf.extend_from_slice(b"meta"));
+blobs.append("blub", b"data");
+assert_eq!(blobs.get("blub"), Some(&b"metadata"[..]));
+blobs.replace("blub", b"new");
+assert_eq!(blobs.get("blub"), Some(&b"new"[..]));
+```
+
+*/
+
+use crate::bytesref::BytesRef;
+use crate::hasher::{StringHasher, Yoshimitsu};
+use crate::pool::private::Storage;
+use crate::vint::{decode_varint_slice, encode_varint, encode_varint_into};
+use crate::{HashedKey, IntoHashedKey, StringHashMap};
+use alloc::vec::Vec;
+use allocator_api2::alloc::{Allocator, Global};
+use core::convert::TryFrom;
+
+/// The keys are the ones of a `StringHashMap`, each value is written to its string data right
+/// after the key. Moving an entry to the end points the table entry of the key to the copy.
+#[derive(Debug)]
+pub struct StringBlobMap<A: Allocator + Clone = Global, H: StringHasher = Yoshimitsu> {
+    map: StringHashMap<(), A, H>,
+    /// bytes of entries which have been copied to the end of the string data
+    unused_bytes: usize,
+    /// reused for the values created by `get_or_create_with` and `append`
+    buf: Vec<u8>,
+}
+
+/// Position of a value in the string data.
+#[derive(Debug, Clone, Copy)]
+struct ValueRange {
+    /// start of the varint length
+    len_pos: usize,
+    start: usize,
+    end: usize,
+}
+
+impl Default for StringBlobMap {
+    fn default() -> Self {
+        StringBlobMap::with_power_of_two_size(10)
+    }
+}
+
+impl StringBlobMap {
+    #[inline]
+    pub fn with_power_of_two_size(power_of_two: usize) -> Self {
+        Self::with_power_of_two_size_and_hasher_in(power_of_two, Yoshimitsu, Global)
+    }
+    #[inline]
+    pub fn new() -> Self {
+        Self::with_power_of_two_size(10)
+    }
+}
+
+impl<A: Allocator + Clone, H: StringHasher> StringBlobMap<A, H> {
+    #[inline]
+    pub fn with_power_of_two_size_and_hasher_in(power_of_two: usize, hasher: H, alloc: A) -> Self {
+        StringBlobMap {
+            map: StringHashMap::with_power_of_two_size_and_hasher_in(power_of_two, hasher, alloc),
+            unused_bytes: 0,
+            buf: Vec::new(),
+        }
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// Returns the number of bytes in the string data which belong to entries that have been
+    /// copied to the end by `append` or `replace`.
+    #[inline]
+    pub fn unused_bytes(&self) -> usize {
+        self.unused_bytes
+    }
+
+    #[inline]
+    pub fn shrink_to_fit(&mut self) {
+        self.map.shrink_to_fit();
+        self.buf = Vec::new();
+    }
+
+    #[inline]
+    pub fn get<'k>(&self, el: impl IntoHashedKey<'k, H>) -> Option<&[u8]> {
+        let el = el.into_hashed_key(&self.map.hasher);
+        self.map
+            .find_entry(el)
+            .map(|entry| self.value(entry.pointer))
+    }
+
+    /// Returns the value of `el`. If `el` is not in the map, `init` writes its value into an
+    /// empty buffer.
+    #[inline]
+    pub fn get_or_create_with<'k, F>(&mut self, el: impl IntoHashedKey<'k, H>, init: F) -> &[u8]
+    where
+        F: FnOnce(&mut Vec<u8>),
+    {
+        let el = el.into_hashed_key(&self.map.hasher);
+        let pointer = match self.get_or_insert_key(el) {
+            Ok(pointer) => pointer,
+            Err(pointer) => {
+                let mut buf = core::mem::take(&mut self.buf);
+                buf.clear();
+                init(&mut buf);
+                self.push_value(&buf);
+                self.buf = buf;
+                pointer
+            }
+        };
+        self.value(pointer)
+    }
+
+    /// Appends `bytes` to the value of `el`, a missing key is inserted with `bytes` as value.
+    pub fn append<'k>(&mut self, el: impl IntoHashedKey<'k, H>, bytes: &[u8]) {
+        let el = el.into_hashed_key(&self.map.hasher);
+        let pointer = match self.get_or_insert_key(el) {
+            Ok(pointer) => pointer,
+            Err(_) => return self.push_value(bytes),
+        };
+        let range = self.value_range(pointer);
+        let new_len = value_len(range.end - range.start + bytes.len());
+        let (len_buf, len_len) = encode_varint(new_len);
+        let string_data = &mut self.map.string_data;
+        if range.end == string_data.len() && len_len == range.start - range.len_pos {
+            string_data[range.len_pos..range.start].copy_from_slice(&len_buf[..len_len]);
+            string_data.extend_from_slice(bytes);
+            return;
+        }
+        let mut buf = core::mem::take(&mut self.buf);
+        buf.clear();
+        buf.extend_from_slice(&string_data[range.start..range.end]);
+        buf.extend_from_slice(bytes);
+        self.move_to_end(el, pointer, range, &buf);
+        self.buf = buf;
+    }
+
+    /// Sets the value of `el` to `bytes`, a missing key is inserted.
+    pub fn replace<'k>(&mut self, el: impl IntoHashedKey<'k, H>, bytes: &[u8]) {
+        let el = el.into_hashed_key(&self.map.hasher);
+        let pointer = match self.get_or_insert_key(el) {
+            Ok(pointer) => pointer,
+            Err(_) => return self.push_value(bytes),
+        };
+        let range = self.value_range(pointer);
+        if range.end - range.start == bytes.len() {
+            self.map.string_data[range.start..range.end].copy_from_slice(bytes);
+            return;
+        }
+        self.move_to_end(el, pointer, range, bytes);
+    }
+
+    /// Returns the keys and values in table order.
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &[u8])> {
+        self.map
+            .table
+            .iter()
+            .filter(|entry| !entry.pointer.is_null())
+            .map(move |entry| {
+                (
+                    self.map.read_string(&entry.pointer),
+                    self.value(entry.pointer),
+                )
+            })
+    }
+
+    /// Returns the keys in table order.
+    #[inline]
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.iter().map(|(key, _)| key)
+    }
+
+    /// Returns `Ok` with the pointer to `el`, or inserts `el` at the end of the string data and
+    /// returns `Err` with its pointer. The value of a new key has to be pushed right after.
+    #[inline]
+    fn get_or_insert_key(&mut self, el: HashedKey<H>) -> Result<BytesRef, BytesRef> {
+        let len = self.map.len();
+        let pointer = self.map.get_or_create_entry(el, ()).pointer;
+        if self.map.len() == len {
+            Ok(pointer)
+        } else {
+            Err(pointer)
+        }
+    }
+
+    /// Writes `bytes` as the value of the last key in the string data.
+    #[inline]
+    fn push_value(&mut self, bytes: &[u8]) {
+        encode_varint_into(&mut self.map.string_data, value_len(bytes.len()));
+        self.map.string_data.extend_from_slice(bytes);
+    }
+
+    /// Copies `el` with the value `bytes` to the end of the string data, and drops the entry at
+    /// `pointer`. The last entry is removed from the string data, others become unused bytes.
+    fn move_to_end(
+        &mut self,
+        el: HashedKey<H>,
+        pointer: BytesRef,
+        range: ValueRange,
+        bytes: &[u8],
+    ) {
+        let string_data = &mut self.map.string_data;
+        if range.end == string_data.len() {
+            string_data.truncate(pointer.addr() as usize);
+        } else {
+            self.unused_bytes += range.end - pointer.addr() as usize;
+        }
+        let new_pointer = Storage::insert(string_data, el.key());
+        self.push_value(bytes);
+        // the key at the old pointer is still the same or has been overwritten by its copy
+        self.map.get_or_create_entry(el, ()).pointer = new_pointer;
+    }
+
+    #[inline]
+    fn value_range(&self, pointer: BytesRef) -> ValueRange {
+        let key_len = self.map.read_string(&pointer).len();
+        let string_data = &self.map.string_data;
+        let mut pos = pointer.addr() as usize;
+        decode_varint_slice(string_data, &mut pos);
+        let len_pos = pos + key_len;
+        pos = len_pos;
+        let value_len = decode_varint_slice(string_data, &mut pos).unwrap();
+        ValueRange {
+            len_pos,
+            start: pos,
+            end: pos + value_len as usize,
+        }
+    }
+
+    #[inline]
+    fn value(&self, pointer: BytesRef) -> &[u8] {
+        let range = self.value_range(pointer);
+        &self.map.string_data[range.start..range.end]
+    }
+}
+
+#[inline]
+fn value_len(len: usize) -> u32 {
+    u32::try_from(len).expect("value is longer than 4GB")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::vec::Vec;
+
+    #[test]
+    fn get_or_create_with() {
+        let mut blobs = StringBlobMap::with_power_of_two_size(1);
+        for i in 0..1000u32 {
+            let value = blobs.get_or_create_with(&i.to_string(), |buf| {
+                buf.extend_from_slice(&i.to_le_bytes())
+            });
+            assert_eq!(value, i.to_le_bytes());
+        }
+        // existing values are not changed
+        let value = blobs.get_or_create_with("10", |buf| buf.push(1));
+        assert_eq!(value, 10u32.to_le_bytes());
+        blobs.get_or_create_with("", |_| {});
+
+        assert_eq!(blobs.len(), 1001);
+        assert_eq!(blobs.get("999"), Some(&999u32.to_le_bytes()[..]));
+        assert_eq!(blobs.get(""), Some(&[][..]));
+        assert_eq!(blobs.get("1000"), None);
+        assert_eq!(blobs.iter().count(), 1001);
+        assert_eq!(blobs.keys().filter(|key| key.len() == 3).count(), 900);
+        assert_eq!(blobs.unused_bytes(), 0);
+    }
+
+    #[test]
+    fn append_and_replace() {
+        let mut blobs = StringBlobMap::new();
+        blobs.append("a", b"1");
+        blobs.append("b", b"2");
+        // last entry, in place
+        blobs.append("b", b"3");
+        assert_eq!(blobs.unused_bytes(), 0);
+        // copied to the end
+        blobs.append("a", b"4");
+        assert_eq!(blobs.unused_bytes(), 4);
+        assert_eq!(blobs.get("a"), Some(&b"14"[..]));
+        assert_eq!(blobs.get("b"), Some(&b"23"[..]));
+
+        // same length, in place
+        blobs.replace("b", b"56");
+        assert_eq!(blobs.get("b"), Some(&b"56"[..]));
+        blobs.replace("b", b"7");
+        assert_eq!(blobs.unused_bytes(), 9);
+        // last entry, truncated
+        blobs.replace("b", b"");
+        blobs.replace("c", b"8");
+        assert_eq!(blobs.unused_bytes(), 9);
+        assert_eq!(blobs.get("a"), Some(&b"14"[..]));
+        assert_eq!(blobs.get("b"), Some(&b""[..]));
+        assert_eq!(blobs.get("c"), Some(&b"8"[..]));
+        assert_eq!(blobs.len(), 3);
+
+        let mut entries = blobs.iter().collect::<Vec<_>>();
+        entries.sort();
+        assert_eq!(
+            entries,
+            &[("a", &b"14"[..]), ("b", &b""[..]), ("c", &b"8"[..])]
+        );
+    }
+
+    #[test]
+    fn with_hasher() {
+        let mut blobs = StringBlobMap::with_power_of_two_size_and_hasher_in(
+            1,
+            crate::hasher::SipHash::with_keys(1, 2),
+            Global,
+        );
+        for i in 0..100u32 {
+            blobs.append(&(i % 10).to_string(), &[i as u8]);
+        }
+        assert_eq!(blobs.len(), 10);
+        assert_eq!(
+            blobs.get("3"),
+            Some(&[3, 13, 23, 33, 43, 53, 63, 73, 83, 93][..])
+        );
+    }
+
+    #[test]
+    fn append_grows_length_prefix() {
+        let mut blobs = StringBlobMap::new();
+        let mut expected = Vec::new();
+        for i in 0..1000u32 {
+            let byte = [i as u8];
+            blobs.append("blub", &byte);
+            expected.push(byte[0]);
+            assert_eq!(blobs.get("blub"), Some(&expected[..]));
+        }
+        // the length prefix grew from one to two bytes at 128
+        assert_eq!(blobs.unused_bytes(), 0);
+        assert_eq!(blobs.map.string_data.len(), 1 + 4 + 2 + 1000);
+    }
+}
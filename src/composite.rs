@@ -0,0 +1,368 @@
+/*! Hashmap with keys made of several strings, e.g. to count n-grams.
+
+`CompositeStringHashMap` takes keys as `&[&str]` and stores them as string keys of a
+`StringHashMap`: the number of parts and then each part, prefixed with its decimal length, with a
+`:` after each number, e.g. `2:3:new4:york`. The encoding of a lookup is written to a buffer on
+the stack, without a temporary `String` unless it is longer than 128 bytes.
+
+# Examples
+```
+use inohashmap::CompositeStringHashMap;
+let mut bigrams = CompositeStringHashMap::<u32>::new();
+let tokens = "a rose is a rose".split_whitespace().collect::<Vec<_>>();
+for bigram in tokens.windows(2) {
+    *bigrams.get_or_create_parts(bigram, 0) += 1;
+}
+assert_eq!(bigrams.get_parts(&["a", "rose"]), Some(&2));
+let (parts, count) = bigrams.iter().max_by_key(|(_, count)| **count).unwrap();
+assert_eq!(parts, ["a", "rose"][..]);
+assert_eq!(parts.iter().collect::<Vec<_>>(), vec!["a", "rose"]);
+```
+
+*/
+
+use crate::hasher::{StringHasher, Yoshimitsu};
+use crate::StringHashMap;
+use alloc::vec::Vec;
+use allocator_api2::alloc::{Allocator, Global};
+use core::fmt::Debug;
+
+/// The keys are the encodings of the parts, so hasher, allocator and resizing are the ones of
+/// `StringHashMap`.
+#[derive(Debug)]
+pub struct CompositeStringHashMap<T, A: Allocator + Clone = Global, H: StringHasher = Yoshimitsu> {
+    map: StringHashMap<T, A, H>,
+}
+
+/// The parts of a key stored in a `CompositeStringHashMap`.
+#[derive(Clone, Copy)]
+pub struct Parts<'a> {
+    /// the encoded key
+    data: &'a str,
+}
+
+impl<'a> Parts<'a> {
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.iter().remaining
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    #[inline]
+    pub fn get(&self, index: usize) -> Option<&'a str> {
+        self.iter().nth(index)
+    }
+
+    #[inline]
+    pub fn iter(&self) -> PartsIter<'a> {
+        let mut data = self.data;
+        let remaining = read_number(&mut data);
+        PartsIter { remaining, data }
+    }
+
+    /// Returns true if the key consists of `parts`.
+    #[inline]
+    fn equals(&self, parts: &[&str]) -> bool {
+        let mut iter = self.iter();
+        parts.iter().all(|part| iter.next() == Some(*part)) && iter.next().is_none()
+    }
+}
+
+impl<'a, 'b> PartialEq<[&'b str]> for Parts<'a> {
+    #[inline]
+    fn eq(&self, other: &[&'b str]) -> bool {
+        self.equals(other)
+    }
+}
+
+impl<'a> Debug for Parts<'a> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<'a> IntoIterator for Parts<'a> {
+    type Item = &'a str;
+    type IntoIter = PartsIter<'a>;
+
+    #[inline]
+    fn into_iter(self) -> PartsIter<'a> {
+        self.iter()
+    }
+}
+
+/// Iterator over the parts of a key.
+#[derive(Debug, Clone)]
+pub struct PartsIter<'a> {
+    remaining: usize,
+    /// the remaining parts
+    data: &'a str,
+}
+
+impl<'a> Iterator for PartsIter<'a> {
+    type Item = &'a str;
+
+    #[inline]
+    fn next(&mut self) -> Option<&'a str> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+        let len = read_number(&mut self.data);
+        let (part, rest) = self.data.split_at(len);
+        self.data = rest;
+        Some(part)
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'a> ExactSizeIterator for PartsIter<'a> {}
+
+/// Reads a number and the `:` after it from the start of `data`.
+#[inline]
+fn read_number(data: &mut &str) -> usize {
+    let colon = data.find(':').unwrap();
+    let num = data[..colon].parse().unwrap();
+    *data = &data[colon + 1..];
+    num
+}
+
+impl<T: Default + Clone + Debug> Default for CompositeStringHashMap<T> {
+    fn default() -> Self {
+        CompositeStringHashMap::with_power_of_two_size(10)
+    }
+}
+
+impl<T: Default + Clone + Debug> CompositeStringHashMap<T> {
+    #[inline]
+    pub fn with_power_of_two_size(power_of_two: usize) -> Self {
+        Self::with_power_of_two_size_and_hasher_in(power_of_two, Yoshimitsu, Global)
+    }
+    #[inline]
+    pub fn new() -> Self {
+        Self::with_power_of_two_size(10)
+    }
+}
+
+impl<T: Default + Clone + Debug, A: Allocator + Clone, H: StringHasher>
+    CompositeStringHashMap<T, A, H>
+{
+    #[inline]
+    pub fn with_power_of_two_size_and_hasher_in(power_of_two: usize, hasher: H, alloc: A) -> Self {
+        CompositeStringHashMap {
+            map: StringHashMap::with_power_of_two_size_and_hasher_in(power_of_two, hasher, alloc),
+        }
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    #[inline]
+    pub fn shrink_to_fit(&mut self) {
+        self.map.shrink_to_fit();
+    }
+
+    #[inline]
+    pub fn get_parts(&self, parts: &[&str]) -> Option<&T> {
+        with_encoded(parts, |key| self.map.get(key))
+    }
+
+    #[inline]
+    pub fn get_parts_mut(&mut self, parts: &[&str]) -> Option<&mut T> {
+        let map = &mut self.map;
+        with_encoded(parts, move |key| map.get_mut(key))
+    }
+
+    #[inline]
+    pub fn get_or_create_parts(&mut self, parts: &[&str], value: T) -> &mut T {
+        let map = &mut self.map;
+        with_encoded(parts, move |key| map.get_or_create(key, value))
+    }
+
+    /// Returns the keys in insertion order.
+    #[inline]
+    pub fn keys(&self) -> impl Iterator<Item = Parts<'_>> {
+        self.map.keys().map(|data| Parts { data })
+    }
+
+    #[inline]
+    pub fn values(&self) -> impl Iterator<Item = &T> {
+        self.map.values()
+    }
+
+    #[inline]
+    pub fn values_mut(&mut self) -> impl Iterator<Item = &mut T> {
+        self.map.values_mut()
+    }
+
+    /// Returns the keys and values in table order.
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = (Parts<'_>, &T)> {
+        self.map.iter().map(|(data, value)| (Parts { data }, value))
+    }
+
+    #[inline]
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (Parts<'_>, &mut T)> {
+        self.map
+            .iter_mut()
+            .map(|(data, value)| (Parts { data }, value))
+    }
+}
+
+/// Keys up to this encoded length are encoded on the stack.
+const STACK_KEY_LEN: usize = 128;
+
+/// Calls `f` with the encoding of `parts`.
+#[inline]
+fn with_encoded<R>(parts: &[&str], f: impl FnOnce(&str) -> R) -> R {
+    let encoded_len = num_digits(parts.len())
+        + 1
+        + parts
+            .iter()
+            .map(|part| num_digits(part.len()) + 1 + part.len())
+            .sum::<usize>();
+    if encoded_len <= STACK_KEY_LEN {
+        let mut buf = [0u8; STACK_KEY_LEN];
+        let mut pos = 0;
+        encode(parts, |bytes| {
+            buf[pos..pos + bytes.len()].copy_from_slice(bytes);
+            pos += bytes.len();
+        });
+        // digits, colons and the bytes of the parts
+        return f(unsafe { core::str::from_utf8_unchecked(&buf[..encoded_len]) });
+    }
+    let mut buf = Vec::with_capacity(encoded_len);
+    encode(parts, |bytes| buf.extend_from_slice(bytes));
+    f(unsafe { core::str::from_utf8_unchecked(&buf) })
+}
+
+/// Writes the encoding of `parts` to `push`.
+#[inline]
+fn encode(parts: &[&str], mut push: impl FnMut(&[u8])) {
+    push_number(parts.len(), &mut push);
+    for part in parts {
+        push_number(part.len(), &mut push);
+        push(part.as_bytes());
+    }
+}
+
+/// Writes `num` in decimal and a `:` to `push`.
+#[inline]
+fn push_number(mut num: usize, push: &mut impl FnMut(&[u8])) {
+    const LEN: usize = 21;
+    let mut digits = [b':'; LEN];
+    let start = LEN - 1 - num_digits(num);
+    for digit in digits[start..LEN - 1].iter_mut().rev() {
+        *digit = b'0' + (num % 10) as u8;
+        num /= 10;
+    }
+    push(&digits[start..]);
+}
+
+#[inline]
+fn num_digits(mut num: usize) -> usize {
+    let mut digits = 1;
+    while num >= 10 {
+        num /= 10;
+        digits += 1;
+    }
+    digits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::test_words;
+    use std::vec::Vec;
+
+    #[test]
+    fn encoding() {
+        let long_part = "rather long part which doesn't fit into the buffer on the stack".repeat(3);
+        for parts in [
+            &["a", "", "b:c", "12"][..],
+            &["a", "", &long_part, "b"],
+            &[],
+        ]
+        .iter()
+        {
+            let mut map = CompositeStringHashMap::<u32>::new();
+            map.get_or_create_parts(parts, 1);
+            let key = map.keys().next().unwrap();
+            assert_eq!(key, **parts);
+            assert_eq!(map.get_parts(parts), Some(&1));
+        }
+        with_encoded(&["new", "york", &"a".repeat(10)], |key| {
+            assert_eq!(key, "3:3:new4:york10:aaaaaaaaaa")
+        });
+    }
+
+    #[test]
+    fn ngrams() {
+        let contents = test_words();
+        let tokens = contents.split_whitespace().collect::<Vec<_>>();
+
+        let mut map = CompositeStringHashMap::<u32>::with_power_of_two_size(1);
+        let mut reference = std::collections::HashMap::<Vec<&str>, u32>::new();
+        for n in 1..=3 {
+            for ngram in tokens.windows(n) {
+                *map.get_or_create_parts(ngram, 0) += 1;
+                *reference.entry(ngram.to_vec()).or_default() += 1;
+            }
+        }
+        assert_eq!(map.len(), reference.len());
+        for (ngram, count) in reference.iter() {
+            assert_eq!(map.get_parts(ngram), Some(count));
+        }
+        for (parts, count) in map.iter() {
+            assert_eq!(reference[&parts.iter().collect::<Vec<_>>()], *count);
+            assert_eq!(parts.iter().len(), parts.len());
+        }
+        // keys are in insertion order, the unigrams come first
+        assert_eq!(map.keys().next().unwrap(), [tokens[0]][..]);
+        assert_eq!(map.keys().count(), map.len());
+        assert_eq!(map.get_parts(&["not", "in", "the", "map"]), None);
+    }
+
+    #[test]
+    fn parts_are_separate() {
+        let mut map = CompositeStringHashMap::<u32>::new();
+        map.get_or_create_parts(&["ab", "c"], 1);
+        map.get_or_create_parts(&["a", "bc"], 2);
+        map.get_or_create_parts(&["abc"], 3);
+        map.get_or_create_parts(&["abc", ""], 4);
+        map.get_or_create_parts(&[], 5);
+        assert_eq!(map.len(), 5);
+        assert_eq!(map.get_parts(&["ab", "c"]), Some(&1));
+        assert_eq!(map.get_parts(&["a", "bc"]), Some(&2));
+        assert_eq!(map.get_parts(&["abc"]), Some(&3));
+        assert_eq!(map.get_parts(&["abc", ""]), Some(&4));
+        assert_eq!(map.get_parts(&[]), Some(&5));
+        *map.get_parts_mut(&["abc"]).unwrap() += 1;
+        for (parts, value) in map.iter_mut() {
+            *value += parts.len() as u32;
+        }
+        assert_eq!(map.values().sum::<u32>(), 1 + 2 + 4 + 4 + 5 + 2 + 2 + 1 + 2);
+
+        let keys = map.keys().collect::<Vec<_>>();
+        assert_eq!(keys[1].get(1), Some("bc"));
+        assert_eq!(keys[1].get(2), None);
+        assert!(keys[4].is_empty());
+        assert_eq!(std::format!("{:?}", keys[3]), r#"["abc", ""]"#);
+        assert_eq!(keys[0].into_iter().collect::<Vec<_>>(), vec!["ab", "c"]);
+    }
+}
@@ -0,0 +1,223 @@
+/*! Hashmap which stores the values in a separate array, in insertion order.
+
+`DenseStringHashMap` is a `StringHashMap<u32>` from each key to the index of its value, the
+values are appended to a `Vec<T>`. The table keeps only a `BytesRef` and the index of the value. Probing touches 8 bytes per bucket regardless of the size of `T`, and
+empty buckets don't waste space for a value. For large `T` the table is much smaller than the one
+of `StringHashMap`, at the cost of a second memory access to read the value of a key.
+
+Keys and values are iterated in insertion order, and the index of a value is a dense id of its
+key. A `KeyHandle` wraps the index. The position of every key in the string data is kept in a
+second array, 4 bytes per key, so the key and the value of a handle are read without hashing.
+
+# Examples
+```
+use inohashmap::DenseStringHashMap;
+let mut hashmap = DenseStringHashMap::<u32>::new();
+*hashmap.get_or_create("blub", 0) += 1;
+*hashmap.get_or_create("bla", 0) += 1;
+*hashmap.get_or_create("blub", 0) += 1;
+assert_eq!(hashmap.get("blub"), Some(&2));
+assert_eq!(hashmap.get_index("bla"), Some(1));
+assert_eq!(hashmap.iter().collect::<Vec<_>>(), vec![("blub", &2), ("bla", &1)]);
+```
+
+*/
+
+use crate::bytesref::BytesRef;
+use crate::hasher::{StringHasher, Yoshimitsu};
+use crate::{IntoHashedKey, StringHashMap};
+use allocator_api2::alloc::{Allocator, Global};
+use allocator_api2::vec::Vec;
+use core::convert::TryFrom;
+use core::fmt::Debug;
+
+/// The table is a `StringHashMap` from the key to the index of its value, so hasher and
+/// allocator are the ones of `StringHashMap`.
+#[derive(Debug)]
+pub struct DenseStringHashMap<T, A: Allocator + Clone = Global, H: StringHasher = Yoshimitsu> {
+    /// index of the value of each key
+    pub(crate) map: StringHashMap<u32, A, H>,
+    /// position of each key in the string data of `map`, in insertion order
+    pub(crate) keys: Vec<BytesRef, A>,
+    /// values in insertion order
+    pub(crate) values: Vec<T, A>,
+}
+
+impl<T: Debug> Default for DenseStringHashMap<T> {
+    fn default() -> Self {
+        DenseStringHashMap::with_power_of_two_size(10)
+    }
+}
+
+impl<T: Debug> DenseStringHashMap<T> {
+    #[inline]
+    pub fn with_power_of_two_size(power_of_two: usize) -> Self {
+        Self::with_power_of_two_size_and_hasher_in(power_of_two, Yoshimitsu, Global)
+    }
+    #[inline]
+    pub fn new() -> Self {
+        Self::with_power_of_two_size(10)
+    }
+}
+
+impl<T: Debug, A: Allocator + Clone, H: StringHasher> DenseStringHashMap<T, A, H> {
+    #[inline]
+    pub fn with_power_of_two_size_and_hasher_in(power_of_two: usize, hasher: H, alloc: A) -> Self {
+        DenseStringHashMap {
+            keys: Vec::new_in(alloc.clone()),
+            values: Vec::new_in(alloc.clone()),
+            map: StringHashMap::with_power_of_two_size_and_hasher_in(power_of_two, hasher, alloc),
+        }
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    #[inline]
+    pub fn shrink_to_fit(&mut self) {
+        self.map.shrink_to_fit();
+        self.keys.shrink_to_fit();
+        self.values.shrink_to_fit();
+    }
+
+    #[inline]
+    pub fn get<'k>(&self, el: impl IntoHashedKey<'k, H>) -> Option<&T> {
+        self.get_index(el).map(|index| &self.values[index])
+    }
+
+    #[inline]
+    pub fn get_mut<'k>(&mut self, el: impl IntoHashedKey<'k, H>) -> Option<&mut T> {
+        self.get_index(el).map(move |index| &mut self.values[index])
+    }
+
+    /// Returns the position of the value of `el` in insertion order, which is also its position
+    /// in `values`.
+    #[inline]
+    pub fn get_index<'k>(&self, el: impl IntoHashedKey<'k, H>) -> Option<usize> {
+        self.map.get(el).map(|index| *index as usize)
+    }
+
+    #[inline]
+    pub fn get_or_create<'k>(&mut self, el: impl IntoHashedKey<'k, H>, value: T) -> &mut T {
+        let index = self.get_or_create_index(el, value) as usize;
+        &mut self.values[index]
+    }
+
+    /// Returns the index of the value of `el`, and appends `value` if `el` is not in the map.
+    #[inline]
+    pub(crate) fn get_or_create_index<'k>(
+        &mut self,
+        el: impl IntoHashedKey<'k, H>,
+        value: T,
+    ) -> u32 {
+        let next_index = u32::try_from(self.values.len()).expect("more than u32::MAX keys");
+        let el = el.into_hashed_key(&self.map.hasher);
+        let entry = self.map.get_or_create_entry(el, next_index);
+        if entry.value == next_index {
+            self.keys.push(entry.pointer);
+            self.values.push(value);
+        }
+        entry.value
+    }
+
+    /// Returns the values in insertion order.
+    #[inline]
+    pub fn values(&self) -> &[T] {
+        &self.values
+    }
+
+    #[inline]
+    pub fn values_mut(&mut self) -> &mut [T] {
+        &mut self.values
+    }
+
+    /// Returns the keys in insertion order.
+    #[inline]
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.map.keys()
+    }
+
+    /// Returns the keys and values in insertion order.
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &T)> {
+        self.map.keys().zip(self.values.iter())
+    }
+
+    #[inline]
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (&str, &mut T)> {
+        self.map.keys().zip(self.values.iter_mut())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{assert_same_values, count_words, test_words};
+    use crate::TableEntry;
+    use std::vec::Vec;
+
+    #[test]
+    fn entry_size() {
+        assert_eq!(std::mem::size_of::<TableEntry<u32>>(), 8);
+    }
+
+    #[test]
+    fn insertion_order() {
+        let mut hashmap = DenseStringHashMap::<[u64; 5]>::with_power_of_two_size(1);
+        let keys = (0..100).map(|i| format!("blub{}", i)).collect::<Vec<_>>();
+        for (i, key) in keys.iter().enumerate() {
+            hashmap.get_or_create(key, [i as u64; 5])[1] += 1;
+        }
+        hashmap.get_or_create("blub0", [0; 5])[1] += 1;
+        hashmap.get_or_create("", [7; 5]);
+
+        assert_eq!(hashmap.len(), 101);
+        assert_eq!(hashmap.get("blub0"), Some(&[0, 2, 0, 0, 0]));
+        assert_eq!(hashmap.get("blub3"), Some(&[3, 4, 3, 3, 3]));
+        assert_eq!(hashmap.get(""), Some(&[7; 5]));
+        assert_eq!(hashmap.get("blub100"), None);
+        assert_eq!(hashmap.get_index("blub42"), Some(42));
+        assert_eq!(hashmap.get_index(""), Some(100));
+        hashmap.get_mut("blub42").unwrap()[0] = 0;
+        assert_eq!(hashmap.values()[42], [0, 43, 42, 42, 42]);
+
+        let iter_keys = hashmap.keys().collect::<Vec<_>>();
+        assert_eq!(
+            iter_keys[..100],
+            keys.iter().map(|key| key.as_str()).collect::<Vec<_>>()[..]
+        );
+        assert_eq!(iter_keys[100], "");
+        for (i, (key, value)) in hashmap.iter().enumerate() {
+            assert_eq!(key, iter_keys[i]);
+            assert_eq!(value, &hashmap.values()[i]);
+        }
+        for (key, value) in hashmap.iter_mut() {
+            value[4] = key.len() as u64;
+        }
+        assert_eq!(hashmap.values()[11][4], 6);
+        for value in hashmap.values_mut() {
+            value[4] += 1;
+        }
+        assert_eq!(hashmap.get("").unwrap()[4], 1);
+    }
+
+    #[test]
+    fn same_as_stringhashmap() {
+        let contents = test_words();
+        let mut map = DenseStringHashMap::<u32>::new();
+        for text in contents.split_whitespace() {
+            *map.get_or_create(text, 0) += 1;
+        }
+        let reference = count_words(&contents);
+        assert_same_values(&reference, map.len(), |key| map.get(key).copied());
+        // insertion order is the order of the string data
+        assert!(map.keys().eq(reference.keys()));
+    }
+}
@@ -0,0 +1,212 @@
+use crate::hasher::{StringHasher, Yoshimitsu};
+use crate::{Allocator, ContiguousKeyStorage, Global, IntoHashedKey, KeyStorage, StringHashMap};
+use allocator_api2::vec::Vec;
+use core::fmt::Debug;
+
+/// A view into a single bucket of a `StringHashMap`, see `StringHashMap::entry`.
+#[derive(Debug)]
+pub enum Entry<
+    'a,
+    T,
+    A: Allocator + Clone = Global,
+    H: StringHasher = Yoshimitsu,
+    S: KeyStorage = Vec<u8, A>,
+> {
+    Occupied(OccupiedEntry<'a, T, A, H, S>),
+    Vacant(VacantEntry<'a, T, A, H, S>),
+}
+
+/// An entry for a key which is in the map.
+#[derive(Debug)]
+pub struct OccupiedEntry<
+    'a,
+    T,
+    A: Allocator + Clone = Global,
+    H: StringHasher = Yoshimitsu,
+    S: KeyStorage = Vec<u8, A>,
+> {
+    map: &'a mut StringHashMap<T, A, H, S>,
+    bucket: usize,
+}
+
+/// An entry for a key which is not in the map.
+#[derive(Debug)]
+pub struct VacantEntry<
+    'a,
+    T,
+    A: Allocator + Clone = Global,
+    H: StringHasher = Yoshimitsu,
+    S: KeyStorage = Vec<u8, A>,
+> {
+    map: &'a mut StringHashMap<T, A, H, S>,
+    bucket: usize,
+    key: &'a str,
+}
+
+impl<T: Default + Clone + Debug, A: Allocator + Clone, H: StringHasher, S: KeyStorage>
+    StringHashMap<T, A, H, S>
+{
+    /// Returns the entry of `el`, which is either occupied or vacant.
+    ///
+    /// # Examples
+    /// ```
+    /// use inohashmap::StringHashMap;
+    /// let mut hashmap = StringHashMap::<u32>::new();
+    /// *hashmap.entry("blub").or_insert(0) += 1;
+    /// *hashmap.entry("blub").or_insert(0) += 1;
+    /// assert_eq!(hashmap.get("blub"), Some(&2));
+    /// ```
+    #[inline]
+    pub fn entry<'a>(&'a mut self, el: impl IntoHashedKey<'a, H>) -> Entry<'a, T, A, H, S> {
+        let el = el.into_hashed_key(&self.hasher);
+        // resize upfront, so the bucket of a vacant entry stays valid
+        self.reserve_one();
+        match self.probe_bucket_mut(el) {
+            Ok(bucket) => Entry::Occupied(OccupiedEntry { map: self, bucket }),
+            Err(bucket) => Entry::Vacant(VacantEntry {
+                bucket: self.check_probe_len(el, bucket),
+                map: self,
+                key: el.key(),
+            }),
+        }
+    }
+}
+
+impl<'a, T: Default + Clone + Debug, A: Allocator + Clone, H: StringHasher, S: KeyStorage>
+    Entry<'a, T, A, H, S>
+{
+    #[inline]
+    pub fn or_insert(self, value: T) -> &'a mut T {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(value),
+        }
+    }
+
+    #[inline]
+    pub fn or_insert_with<F: FnOnce() -> T>(self, value: F) -> &'a mut T {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(value()),
+        }
+    }
+
+    #[inline]
+    pub fn or_default(self) -> &'a mut T {
+        self.or_insert_with(T::default)
+    }
+
+    #[inline]
+    pub fn and_modify<F: FnOnce(&mut T)>(mut self, f: F) -> Self {
+        if let Entry::Occupied(entry) = &mut self {
+            f(entry.get_mut());
+        }
+        self
+    }
+}
+
+impl<
+        'a,
+        T: Default + Clone + Debug,
+        A: Allocator + Clone,
+        H: StringHasher,
+        S: ContiguousKeyStorage,
+    > Entry<'a, T, A, H, S>
+{
+    #[inline]
+    pub fn key(&self) -> &str {
+        match self {
+            Entry::Occupied(entry) => entry.key(),
+            Entry::Vacant(entry) => entry.key(),
+        }
+    }
+}
+
+impl<
+        'a,
+        T: Default + Clone + Debug,
+        A: Allocator + Clone,
+        H: StringHasher,
+        S: ContiguousKeyStorage,
+    > OccupiedEntry<'a, T, A, H, S>
+{
+    #[inline]
+    pub fn key(&self) -> &str {
+        self.map
+            .read_string(&self.map.get_entry(self.bucket).pointer)
+    }
+}
+
+impl<'a, T: Default + Clone + Debug, A: Allocator + Clone, H: StringHasher, S: KeyStorage>
+    OccupiedEntry<'a, T, A, H, S>
+{
+    #[inline]
+    pub fn get(&self) -> &T {
+        &self.map.get_entry(self.bucket).value
+    }
+
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.map.get_entry_mut(self.bucket).value
+    }
+
+    #[inline]
+    pub fn into_mut(self) -> &'a mut T {
+        &mut self.map.get_entry_mut(self.bucket).value
+    }
+}
+
+impl<'a, T: Default + Clone + Debug, A: Allocator + Clone, H: StringHasher, S: KeyStorage>
+    VacantEntry<'a, T, A, H, S>
+{
+    #[inline]
+    pub fn key(&self) -> &str {
+        self.key
+    }
+
+    #[inline]
+    pub fn insert(self, value: T) -> &'a mut T {
+        self.map.occupied += 1;
+        &mut self.map.put_in_bucket(self.bucket, self.key, value).value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Entry, HashedKey, StringHashMap};
+
+    #[test]
+    fn entry() {
+        let mut hashmap = StringHashMap::<u32>::with_power_of_two_size(1);
+        for i in 0..100 {
+            let key = format!("blub{}", i % 10);
+            *hashmap.entry(&key).or_insert(0) += 1;
+        }
+        assert_eq!(hashmap.len(), 10);
+        assert!(hashmap.values().all(|value| *value == 10));
+
+        match hashmap.entry("blub1") {
+            Entry::Occupied(entry) => {
+                assert_eq!(entry.key(), "blub1");
+                assert_eq!(entry.get(), &10);
+            }
+            Entry::Vacant(_) => panic!("blub1 is in the map"),
+        }
+        match hashmap.entry(HashedKey::new("blub10")) {
+            Entry::Occupied(_) => panic!("blub10 is not in the map"),
+            Entry::Vacant(entry) => {
+                assert_eq!(entry.key(), "blub10");
+                *entry.insert(5) += 1;
+            }
+        }
+        assert_eq!(hashmap.get("blub10"), Some(&6));
+
+        hashmap.entry("blub10").and_modify(|value| *value += 1);
+        hashmap.entry("blub11").and_modify(|value| *value += 1);
+        assert_eq!(hashmap.get("blub10"), Some(&7));
+        assert_eq!(hashmap.get("blub11"), None);
+        assert_eq!(hashmap.entry("blub11").or_default(), &0);
+        assert_eq!(hashmap.entry("blub12").or_insert_with(|| 12), &12);
+        assert_eq!(hashmap.len(), 13);
+    }
+}
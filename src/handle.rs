@@ -0,0 +1,131 @@
+//! Stable handles to keys of a `DenseStringHashMap`.
+
+use crate::dense::DenseStringHashMap;
+use crate::hasher::StringHasher;
+use crate::{Allocator, IntoHashedKey};
+use core::fmt::Debug;
+
+/// 4 byte handle to a key in a `DenseStringHashMap`.
+///
+/// The handle is the index of the key in insertion order, which is also the position of its
+/// value. It never changes, so handles stay valid when the table is resized, and the key and the
+/// value of a handle are read without hashing or probing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct KeyHandle(u32);
+
+impl KeyHandle {
+    #[inline]
+    fn index(self) -> usize {
+        self.0 as usize
+    }
+
+    /// Returns the handle as `u32`, e.g. to store it in a compact format.
+    #[inline]
+    pub fn as_u32(self) -> u32 {
+        self.0
+    }
+
+    /// Creates a handle from the value returned by `as_u32`.
+    #[inline]
+    pub fn from_u32(value: u32) -> KeyHandle {
+        KeyHandle(value)
+    }
+}
+
+impl<T: Debug, A: Allocator + Clone, H: StringHasher> DenseStringHashMap<T, A, H> {
+    /// Like `get_or_create`, but also returns a handle to the key.
+    #[inline]
+    pub fn get_or_create_handle<'k>(
+        &mut self,
+        el: impl IntoHashedKey<'k, H>,
+        value: T,
+    ) -> (KeyHandle, &mut T) {
+        let index = self.get_or_create_index(el, value);
+        (KeyHandle(index), &mut self.values[index as usize])
+    }
+
+    /// Returns the handle of `el`, if it is in the map.
+    #[inline]
+    pub fn get_handle<'k>(&self, el: impl IntoHashedKey<'k, H>) -> Option<KeyHandle> {
+        self.map.get(el).map(|index| KeyHandle(*index))
+    }
+
+    /// Returns the key of `handle`.
+    ///
+    /// # Panics
+    /// Panics if the map has fewer keys than the index of `handle`. A handle of a different map
+    /// may also be the index of an unrelated key, which is returned.
+    #[inline]
+    pub fn key(&self, handle: KeyHandle) -> &str {
+        // every pointer in `keys` has been returned by the string data of this map
+        self.map.read_string(&self.keys[handle.index()])
+    }
+
+    /// Returns the value of `handle`.
+    ///
+    /// # Panics
+    /// Panics if the map has fewer keys than the index of `handle`.
+    #[inline]
+    pub fn value(&self, handle: KeyHandle) -> &T {
+        &self.values[handle.index()]
+    }
+
+    /// Returns the value of `handle` mutably.
+    ///
+    /// # Panics
+    /// Panics if the map has fewer keys than the index of `handle`.
+    #[inline]
+    pub fn value_mut(&mut self, handle: KeyHandle) -> &mut T {
+        &mut self.values[handle.index()]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::DenseStringHashMap;
+    use std::vec::Vec;
+
+    #[test]
+    fn handles_survive_resize() {
+        let mut hashmap = DenseStringHashMap::<u32>::with_power_of_two_size(1);
+        let handles = (0..100)
+            .map(|i| hashmap.get_or_create_handle(&format!("blub{}", i), i).0)
+            .collect::<Vec<_>>();
+
+        for (i, handle) in handles.iter().enumerate() {
+            assert_eq!(handle.as_u32(), i as u32);
+            assert_eq!(hashmap.key(*handle), format!("blub{}", i));
+            assert_eq!(hashmap.value(*handle), &(i as u32));
+            *hashmap.value_mut(*handle) += 1;
+            assert_eq!(hashmap.get_handle(&format!("blub{}", i)), Some(*handle));
+            assert_eq!(
+                hashmap.get_or_create_handle(&format!("blub{}", i), 0).0,
+                *handle
+            );
+        }
+        assert_eq!(hashmap.get("blub5"), Some(&6));
+        assert_eq!(hashmap.get_handle("blub100"), None);
+    }
+
+    #[test]
+    #[should_panic]
+    fn foreign_handle() {
+        let mut hashmap = DenseStringHashMap::<u32>::new();
+        hashmap.get_or_create("blub1", 1);
+        let mut other = DenseStringHashMap::<u32>::new();
+        other.get_or_create("blub2", 2);
+        let (handle, _) = other.get_or_create_handle("blub1", 3);
+        hashmap.value(handle);
+    }
+
+    #[test]
+    fn foreign_handle_of_other_key() {
+        let mut hashmap = DenseStringHashMap::<u32>::new();
+        hashmap.get_or_create("blub", 1);
+        let mut other = DenseStringHashMap::<u32>::new();
+        let (handle, _) = other.get_or_create_handle("bla", 2);
+        // both keys have the first index
+        assert_eq!(hashmap.key(handle), "blub");
+        assert_eq!(hashmap.value(handle), &1);
+    }
+}
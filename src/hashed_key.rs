@@ -0,0 +1,161 @@
+use crate::hasher::{StringHasher, Yoshimitsu};
+use alloc::string::String;
+use core::fmt::{self, Debug};
+use core::marker::PhantomData;
+
+/// A key together with its hash.
+///
+/// Computing the `HashedKey` once allows to look up the same key in several maps without hashing
+/// it again. All methods taking a key accept a `HashedKey` as well as a `&str`, see
+/// `IntoHashedKey`.
+///
+/// The type parameter is the hasher of the hash, a key can only be passed to maps with the same
+/// hasher type. Keys of a seeded hasher like `SipHash` are only valid for maps with the same
+/// seed, create them with `StringHashMap::hash_key` of the map.
+///
+/// # Examples
+/// ```
+/// use inohashmap::{HashedKey, StringHashMap};
+/// let mut title = StringHashMap::<u32>::new();
+/// let mut body = StringHashMap::<u32>::new();
+/// let key = HashedKey::new("blub");
+/// *title.get_or_create(key, 0) += 1;
+/// *body.get_or_create(key, 0) += 1;
+/// assert_eq!(title.get(key), Some(&1));
+/// ```
+///
+/// A key hashed with the default hasher doesn't fit a map with another hasher:
+/// ```compile_fail
+/// use inohashmap::hasher::SipHash;
+/// use inohashmap::{HashedKey, StringHashMap};
+/// let hashmap = StringHashMap::<u32, _, _>::with_hasher(SipHash::with_keys(1, 2));
+/// hashmap.get(HashedKey::new("blub"));
+/// ```
+pub struct HashedKey<'a, H = Yoshimitsu> {
+    key: &'a str,
+    hash: u64,
+    hasher: PhantomData<fn() -> H>,
+}
+
+impl<'a, H> Clone for HashedKey<'a, H> {
+    #[inline]
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'a, H> Copy for HashedKey<'a, H> {}
+
+impl<'a, H> PartialEq for HashedKey<'a, H> {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        self.key == other.key && self.hash == other.hash
+    }
+}
+
+impl<'a, H> Eq for HashedKey<'a, H> {}
+
+impl<'a, H> Debug for HashedKey<'a, H> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HashedKey")
+            .field("key", &self.key)
+            .field("hash", &self.hash)
+            .finish()
+    }
+}
+
+impl<'a> HashedKey<'a> {
+    /// Hashes `key` with `fnv32a_yoshimitsu_hasher`, the hash of the default hasher. For maps with
+    /// another hasher use `with_hasher` or `StringHashMap::hash_key`.
+    #[inline]
+    pub fn new(key: &'a str) -> Self {
+        HashedKey::with_hasher(key, &Yoshimitsu)
+    }
+}
+
+impl<'a, H: StringHasher> HashedKey<'a, H> {
+    /// Hashes `key` with `hasher`.
+    #[inline]
+    pub fn with_hasher(key: &'a str, hasher: &H) -> Self {
+        HashedKey::from_hash64(key, hasher.hash64(key.as_bytes()))
+    }
+
+    /// Creates a key with a hash computed by the caller, e.g. when the hash is needed anyway to
+    /// route the key to a shard.
+    ///
+    /// `hash` has to be the hash the map would compute for `key`, otherwise the key is not found
+    /// or inserted twice. Maps with up to 2^31 buckets only use these 32 bits, larger maps with a
+    /// 64 bit hasher need `from_hash64`.
+    #[inline]
+    pub fn from_hash(key: &'a str, hash: u32) -> Self {
+        HashedKey::from_hash64(key, u64::from(hash) << 32)
+    }
+
+    /// Like `from_hash`, with the hash of `StringHasher::hash64`.
+    #[inline]
+    pub fn from_hash64(key: &'a str, hash: u64) -> Self {
+        HashedKey {
+            key,
+            hash,
+            hasher: PhantomData,
+        }
+    }
+
+    #[inline]
+    pub fn key(&self) -> &'a str {
+        self.key
+    }
+
+    /// Returns the 32 bit hash, which is the high half of `hash64`.
+    #[inline]
+    pub fn hash(&self) -> u32 {
+        (self.hash >> 32) as u32
+    }
+
+    #[inline]
+    pub fn hash64(&self) -> u64 {
+        self.hash
+    }
+}
+
+/// A key accepted by the methods of `StringHashMap`.
+///
+/// Strings are hashed with the hasher of the map, a `HashedKey` is used as is.
+pub trait IntoHashedKey<'a, H: StringHasher> {
+    fn into_hashed_key(self, hasher: &H) -> HashedKey<'a, H>;
+}
+
+impl<'a, H: StringHasher> IntoHashedKey<'a, H> for HashedKey<'a, H> {
+    #[inline]
+    fn into_hashed_key(self, _hasher: &H) -> HashedKey<'a, H> {
+        self
+    }
+}
+
+impl<'a, H: StringHasher> IntoHashedKey<'a, H> for &'a str {
+    #[inline]
+    fn into_hashed_key(self, hasher: &H) -> HashedKey<'a, H> {
+        HashedKey::with_hasher(self, hasher)
+    }
+}
+
+impl<'a, H: StringHasher> IntoHashedKey<'a, H> for &'a String {
+    #[inline]
+    fn into_hashed_key(self, hasher: &H) -> HashedKey<'a, H> {
+        HashedKey::with_hasher(self, hasher)
+    }
+}
+
+impl<'a> From<&'a str> for HashedKey<'a> {
+    #[inline]
+    fn from(key: &'a str) -> Self {
+        HashedKey::new(key)
+    }
+}
+
+impl<'a> From<&'a String> for HashedKey<'a> {
+    #[inline]
+    fn from(key: &'a String) -> Self {
+        HashedKey::new(key)
+    }
+}
@@ -1,42 +1,76 @@
-use std::ptr::copy_nonoverlapping;
+//! Hash functions for string keys.
+//!
+//! Words are read as little endian on every target, so hashes are the same on little and big
+//! endian platforms.
+//!
+//! # Stability
+//! The output of `fnv32a_yoshimitsu_hasher` and `fnv32a_yoshimitsu_triad` is stable across
+//! crate versions: every version of this crate returns the same hash for the same input and seed,
+//! on every platform. Hashes can therefore be persisted, e.g. to route keys or to rebuild a table
+//! elsewhere. The `test_vectors` test enforces this, its `TEST_VECTORS` were computed with the
+//! first version of the hash and must never be changed.
+
+mod murmur2;
+#[cfg(test)]
+mod quality;
+mod siphash;
+mod wyhash;
+mod xxhash32;
+
+pub use self::murmur2::{murmurhash2, murmurhash2_seeded};
+pub use self::siphash::siphash13;
+pub use self::wyhash::{wyhash, wyhash32};
+pub use self::xxhash32::xxhash32;
+
+use alloc::vec::Vec;
+use core::fmt::Debug;
+use core::hash::{BuildHasher, Hasher};
+use core::ptr::copy_nonoverlapping;
+
+const PRIME: u32 = 709_607;
+const OFFSET_BASIS: u32 = 2_166_136_261;
+/// Seed of `fnv32a_yoshimitsu_hasher`.
+pub(crate) const DEFAULT_SEED: u32 = 0xD8AF_FD71;
 
 #[inline]
 fn rotl32(x: u32, r: u8) -> u32 {
-    (x << r) | (x >> (32 - r))
+    x.rotate_left(u32::from(r))
 }
 
 #[inline]
 fn read_u32_p(p: *const u8) -> u32 {
-    let mut out: u32 = 0;
+    let mut out = [0u8; 4];
     unsafe {
-        copy_nonoverlapping(p, &mut out as *mut u32 as *mut u8, 4);
+        copy_nonoverlapping(p, out.as_mut_ptr(), 4);
     }
-    out
+    u32::from_le_bytes(out)
 }
 
 #[inline]
 fn read_u16_p(p: *const u8) -> u16 {
-    let mut out: u16 = 0;
+    let mut out = [0u8; 2];
     unsafe {
-        copy_nonoverlapping(p, &mut out as *mut u16 as *mut u8, 2);
+        copy_nonoverlapping(p, out.as_mut_ptr(), 2);
     }
-    out
+    u16::from_le_bytes(out)
 }
 
+/// Hashes `bytes` with `fnv32a_yoshimitsu_triad` and the default seed. This is the hash used by
+/// the maps and `HashedKey`.
 #[inline(never)]
 pub fn fnv32a_yoshimitsu_hasher(bytes: &[u8]) -> u32 {
-    fnv32a_yoshimitsu_triad(0xD8AF_FD71, bytes)
+    fnv32a_yoshimitsu_triad(DEFAULT_SEED, bytes)
 }
 
+/// FNV-1a-Yoshimitsu-TRIAD, which hashes three 8 byte lanes per round.
 #[inline]
 pub fn fnv32a_yoshimitsu_triad(seed: u32, bytes: &[u8]) -> u32 {
     let mut len: u32 = bytes.len() as u32;
     let mut p: *const u8 = bytes.as_ptr();
 
-    const PRIME: u32 = 709_607;
-    let mut hash32_a: u32 = seed ^ 2_166_136_261;
-    let mut hash32_b: u32 = 2_166_136_261 + len;
-    let mut hash32_c: u32 = 2_166_136_261;
+    let mut hash32_a: u32 = seed ^ OFFSET_BASIS;
+    let mut hash32_b: u32 = OFFSET_BASIS + len;
+    let mut hash32_c: u32 = OFFSET_BASIS;
     while len >= 24 {
         hash32_a = (hash32_a ^ (rotl32(read_u32_p(p), 5) ^ read_u32_p(unsafe { p.offset(4) })))
             .wrapping_mul(PRIME);
@@ -88,3 +122,469 @@ pub fn fnv32a_yoshimitsu_triad(seed: u32, bytes: &[u8]) -> u32 {
     hash32_a = (hash32_a ^ rotl32(hash32_b, 5)).wrapping_mul(PRIME);
     hash32_a ^ (hash32_a >> 16)
 }
+
+/// Hashes the keys of a `StringHashMap`, see its `H` type parameter.
+///
+/// All hashers read words as little endian and are stable like `fnv32a_yoshimitsu_hasher`.
+pub trait StringHasher: Clone + Debug {
+    fn hash(&self, bytes: &[u8]) -> u32;
+
+    /// The hash used by `StringHashMap`, whose high bits select the bucket. `hash` has to return
+    /// the high half of it.
+    ///
+    /// The default puts the 32 bit hash into the high half, so tables with more than 2^31
+    /// buckets use only every other bucket as start of a probe sequence. Hashers with a 64 bit
+    /// result return it here, which keeps very large tables evenly filled.
+    #[inline]
+    fn hash64(&self, bytes: &[u8]) -> u64 {
+        u64::from(self.hash(bytes)) << 32
+    }
+
+    /// Switches to a new seed which can't be guessed from the hashes of the current one, see
+    /// `StringHashMap::set_max_probe_len`. Returns false if the hasher has no seed.
+    #[inline]
+    fn reseed(&mut self) -> bool {
+        false
+    }
+}
+
+/// `fnv32a_yoshimitsu_hasher`, the default hasher.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Yoshimitsu;
+
+impl StringHasher for Yoshimitsu {
+    #[inline]
+    fn hash(&self, bytes: &[u8]) -> u32 {
+        fnv32a_yoshimitsu_hasher(bytes)
+    }
+}
+
+/// `murmurhash2`, the hash of tantivy's term hashmap.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Murmur2;
+
+impl StringHasher for Murmur2 {
+    #[inline]
+    fn hash(&self, bytes: &[u8]) -> u32 {
+        murmurhash2(bytes)
+    }
+}
+
+/// `xxhash32` with seed 0.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct XxHash32;
+
+impl StringHasher for XxHash32 {
+    #[inline]
+    fn hash(&self, bytes: &[u8]) -> u32 {
+        xxhash32(0, bytes)
+    }
+}
+
+/// `wyhash` with seed 0, a 64 bit hash. Uses 64 bit multiplications, which are slow on 32 bit
+/// targets.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct WyHash;
+
+impl StringHasher for WyHash {
+    #[inline]
+    fn hash(&self, bytes: &[u8]) -> u32 {
+        (self.hash64(bytes) >> 32) as u32
+    }
+
+    #[inline]
+    fn hash64(&self, bytes: &[u8]) -> u64 {
+        wyhash(0, bytes)
+    }
+}
+
+/// `siphash13` with secret keys, which makes it hard to find keys with colliding hashes without
+/// knowing the keys. Slower than the other hashers, use it for keys controlled by users.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SipHash {
+    k0: u64,
+    k1: u64,
+}
+
+impl SipHash {
+    #[inline]
+    pub fn with_keys(k0: u64, k1: u64) -> Self {
+        SipHash { k0, k1 }
+    }
+
+    /// Uses random keys from the std `RandomState`.
+    ///
+    /// # Examples
+    /// ```
+    /// use inohashmap::hasher::SipHash;
+    /// use inohashmap::StringHashMap;
+    /// let mut hashmap = StringHashMap::<u32, _, _>::with_hasher(SipHash::random());
+    /// hashmap.set_max_probe_len(Some(32));
+    /// hashmap.get_or_create("blub", 1);
+    /// assert_eq!(hashmap.get("blub"), Some(&1));
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn random() -> Self {
+        use std::collections::hash_map::RandomState;
+        let state = RandomState::new();
+        let mut k0 = state.build_hasher();
+        k0.write_u8(0);
+        let mut k1 = state.build_hasher();
+        k1.write_u8(1);
+        SipHash::with_keys(k0.finish(), k1.finish())
+    }
+}
+
+impl StringHasher for SipHash {
+    #[inline]
+    fn hash(&self, bytes: &[u8]) -> u32 {
+        (self.hash64(bytes) >> 32) as u32
+    }
+
+    #[inline]
+    fn hash64(&self, bytes: &[u8]) -> u64 {
+        siphash13(self.k0, self.k1, bytes)
+    }
+
+    /// Derives the new keys from the current ones, which are secret.
+    #[inline]
+    fn reseed(&mut self) -> bool {
+        *self = SipHash::with_keys(
+            siphash13(self.k0, self.k1, b"reseed k0"),
+            siphash13(self.k0, self.k1, b"reseed k1"),
+        );
+        true
+    }
+}
+
+/// Streaming version of `fnv32a_yoshimitsu_triad`, for use in `std::collections::HashMap` and
+/// other maps which take a `BuildHasher`.
+///
+/// The hash of all bytes passed to `write` is the same as the one-shot function over their
+/// concatenation, regardless of how the bytes are split into calls. Lane b of the hash starts
+/// with the total length, so it is only computed in `finish`, and one word per 24 byte block is
+/// kept until then. Inputs up to 24 bytes don't allocate.
+///
+/// `finish` returns the 32 bit hash zero-extended to `u64`.
+///
+/// # Examples
+/// ```
+/// use inohashmap::hasher::{fnv32a_yoshimitsu_hasher, YoshimitsuHasher};
+/// use std::hash::Hasher;
+/// let mut hasher = YoshimitsuHasher::default();
+/// hasher.write(b"blub");
+/// hasher.write(b"blub");
+/// assert_eq!(hasher.finish(), u64::from(fnv32a_yoshimitsu_hasher(b"blubblub")));
+/// ```
+#[derive(Debug, Clone)]
+pub struct YoshimitsuHasher {
+    hash32_a: u32,
+    hash32_c: u32,
+    /// mixed words of lane b, one per block
+    lane_b: Vec<u32>,
+    /// lane b, if the number of bytes is known in advance
+    hash32_b: Option<u32>,
+    /// bytes which don't fill a block yet
+    buf: [u8; 24],
+    buf_len: usize,
+    len: u32,
+}
+
+impl YoshimitsuHasher {
+    #[inline]
+    pub fn with_seed(seed: u32) -> Self {
+        YoshimitsuHasher {
+            hash32_a: seed ^ OFFSET_BASIS,
+            hash32_c: OFFSET_BASIS,
+            lane_b: Vec::new(),
+            hash32_b: None,
+            buf: [0; 24],
+            buf_len: 0,
+            len: 0,
+        }
+    }
+
+    /// Creates a hasher for exactly `len` bytes, other numbers of bytes result in a wrong hash.
+    /// Lane b starts with the length, knowing it in advance avoids buffering a word per 24 bytes.
+    #[inline]
+    pub fn with_seed_and_len(seed: u32, len: u32) -> Self {
+        YoshimitsuHasher {
+            hash32_b: Some(OFFSET_BASIS.wrapping_add(len)),
+            ..YoshimitsuHasher::with_seed(seed)
+        }
+    }
+
+    #[inline]
+    fn write_block(&mut self, block: &[u8]) {
+        self.hash32_a = (self.hash32_a ^ (rotl32(read_u32(block, 0), 5) ^ read_u32(block, 4)))
+            .wrapping_mul(PRIME);
+        let word = rotl32(read_u32(block, 8), 5) ^ read_u32(block, 12);
+        match self.hash32_b.as_mut() {
+            Some(hash32_b) => *hash32_b = (*hash32_b ^ word).wrapping_mul(PRIME),
+            None => self.lane_b.push(word),
+        }
+        self.hash32_c = (self.hash32_c ^ (rotl32(read_u32(block, 16), 5) ^ read_u32(block, 20)))
+            .wrapping_mul(PRIME);
+    }
+
+    /// Returns the 32 bit hash of the bytes written so far.
+    pub fn finish32(&self) -> u32 {
+        let mut hash32_a = self.hash32_a;
+        let mut hash32_b = match self.hash32_b {
+            Some(hash32_b) => hash32_b,
+            None => self
+                .lane_b
+                .iter()
+                .fold(OFFSET_BASIS.wrapping_add(self.len), |hash32_b, word| {
+                    (hash32_b ^ word).wrapping_mul(PRIME)
+                }),
+        };
+        // like `p == bytes.as_ptr()` of the one-shot function, no block has been written
+        if self.len < 24 {
+            hash32_a = (hash32_a ^ rotl32(self.hash32_c, 5)).wrapping_mul(PRIME);
+        }
+
+        let tail = &self.buf[..self.buf_len];
+        let mut pos = 0;
+        if (tail.len() & 16) != 0 {
+            hash32_a =
+                (hash32_a ^ (rotl32(read_u32(tail, 0), 5) ^ read_u32(tail, 4))).wrapping_mul(PRIME);
+            hash32_b = (hash32_b ^ (rotl32(read_u32(tail, 8), 5) ^ read_u32(tail, 12)))
+                .wrapping_mul(PRIME);
+            pos += 16;
+        }
+        if (tail.len() & 8) != 0 {
+            hash32_a = (hash32_a ^ read_u32(tail, pos)).wrapping_mul(PRIME);
+            hash32_b = (hash32_b ^ read_u32(tail, pos + 4)).wrapping_mul(PRIME);
+            pos += 8;
+        }
+        if (tail.len() & 4) != 0 {
+            hash32_a = (hash32_a ^ u32::from(read_u16(tail, pos))).wrapping_mul(PRIME);
+            hash32_b = (hash32_b ^ u32::from(read_u16(tail, pos + 2))).wrapping_mul(PRIME);
+            pos += 4;
+        }
+        if (tail.len() & 2) != 0 {
+            hash32_a = (hash32_a ^ u32::from(read_u16(tail, pos))).wrapping_mul(PRIME);
+            pos += 2;
+        }
+        if (tail.len() & 1) != 0 {
+            hash32_a = (hash32_a ^ u32::from(tail[pos])).wrapping_mul(PRIME);
+        }
+        hash32_a = (hash32_a ^ rotl32(hash32_b, 5)).wrapping_mul(PRIME);
+        hash32_a ^ (hash32_a >> 16)
+    }
+}
+
+impl Default for YoshimitsuHasher {
+    /// Uses the seed of `fnv32a_yoshimitsu_hasher`.
+    #[inline]
+    fn default() -> Self {
+        YoshimitsuHasher::with_seed(DEFAULT_SEED)
+    }
+}
+
+impl Hasher for YoshimitsuHasher {
+    fn write(&mut self, mut bytes: &[u8]) {
+        self.len = self.len.wrapping_add(bytes.len() as u32);
+        if self.buf_len != 0 {
+            let num_bytes = bytes.len().min(24 - self.buf_len);
+            self.buf[self.buf_len..self.buf_len + num_bytes].copy_from_slice(&bytes[..num_bytes]);
+            self.buf_len += num_bytes;
+            bytes = &bytes[num_bytes..];
+            if self.buf_len < 24 {
+                return;
+            }
+            let block = self.buf;
+            self.write_block(&block);
+            self.buf_len = 0;
+        }
+        let mut blocks = bytes.chunks_exact(24);
+        for block in &mut blocks {
+            self.write_block(block);
+        }
+        let rest = blocks.remainder();
+        self.buf[..rest.len()].copy_from_slice(rest);
+        self.buf_len = rest.len();
+    }
+
+    #[inline]
+    fn finish(&self) -> u64 {
+        u64::from(self.finish32())
+    }
+}
+
+/// Creates `YoshimitsuHasher`s with a fixed seed.
+///
+/// # Examples
+/// ```
+/// use inohashmap::hasher::BuildYoshimitsuHasher;
+/// use std::collections::HashMap;
+/// let mut map = HashMap::with_hasher(BuildYoshimitsuHasher::with_seed(42));
+/// map.insert("blub", 1);
+/// assert_eq!(map.get("blub"), Some(&1));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BuildYoshimitsuHasher {
+    seed: u32,
+}
+
+impl BuildYoshimitsuHasher {
+    #[inline]
+    pub fn with_seed(seed: u32) -> Self {
+        BuildYoshimitsuHasher { seed }
+    }
+
+    #[inline]
+    pub fn seed(&self) -> u32 {
+        self.seed
+    }
+}
+
+impl Default for BuildYoshimitsuHasher {
+    /// Uses the seed of `fnv32a_yoshimitsu_hasher`.
+    #[inline]
+    fn default() -> Self {
+        BuildYoshimitsuHasher::with_seed(DEFAULT_SEED)
+    }
+}
+
+impl BuildHasher for BuildYoshimitsuHasher {
+    type Hasher = YoshimitsuHasher;
+
+    #[inline]
+    fn build_hasher(&self) -> YoshimitsuHasher {
+        YoshimitsuHasher::with_seed(self.seed)
+    }
+}
+
+#[inline]
+fn read_u32(bytes: &[u8], pos: usize) -> u32 {
+    u32::from_le_bytes([bytes[pos], bytes[pos + 1], bytes[pos + 2], bytes[pos + 3]])
+}
+
+#[inline]
+fn read_u16(bytes: &[u8], pos: usize) -> u16 {
+    u16::from_le_bytes([bytes[pos], bytes[pos + 1]])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Frozen outputs of the first version of the hash, which enforce the stability guarantee of
+    /// the module documentation. Never change them. The inputs cover every length branch.
+    const TEST_VECTORS: &[(&str, u32)] = &[
+        ("", 0x857D_F1C9),
+        ("a", 0x3D1D_85C0),
+        ("ab", 0x5926_CA9B),
+        ("abc", 0x232D_C315),
+        ("abcd", 0x1542_C94B),
+        ("abcde", 0xD7BA_C3B0),
+        ("abcdefg", 0x163E_E9EF),
+        ("abcdefgh", 0x15CA_2021),
+        ("abcdefghijklmno", 0xE995_1C71),
+        ("abcdefghijklmnop", 0xE99C_255A),
+        ("abcdefghijklmnopqrstuvw", 0x4CC9_579F),
+        ("abcdefghijklmnopqrstuvwx", 0x35C7_2AD9),
+        ("The quick brown fox jumps over the lazy dog", 0xA65D_B3DF),
+        ("Schrödinger's café ☕", 0x16D7_AD54),
+    ];
+
+    #[test]
+    fn test_vectors() {
+        for (input, hash) in TEST_VECTORS {
+            assert_eq!(
+                fnv32a_yoshimitsu_hasher(input.as_bytes()),
+                *hash,
+                "{:?}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn test_vectors_seeded() {
+        assert_eq!(fnv32a_yoshimitsu_triad(1, b""), 0xE593_83D7);
+        assert_eq!(fnv32a_yoshimitsu_triad(1, b"a"), 0x2E30_11FD);
+        assert_eq!(fnv32a_yoshimitsu_triad(1, b"ab"), 0xCD84_6D69);
+        assert_eq!(fnv32a_yoshimitsu_triad(1, b"abc"), 0x35AC_C124);
+    }
+
+    #[test]
+    fn streaming_same_as_one_shot() {
+        let text = "The quick brown fox jumps over the lazy dog, Schrödinger's café ☕ \
+                    and a few more words to get past several blocks of 24 bytes.";
+        for len in 0..text.len() {
+            let bytes = &text.as_bytes()[..len];
+            let expected = fnv32a_yoshimitsu_triad(7, bytes);
+            for chunk_size in 1..30 {
+                let mut hasher = BuildYoshimitsuHasher::with_seed(7).build_hasher();
+                for chunk in bytes.chunks(chunk_size) {
+                    hasher.write(chunk);
+                }
+                assert_eq!(hasher.finish32(), expected, "{} {}", len, chunk_size);
+            }
+            // uneven chunks
+            let mut hasher = YoshimitsuHasher::with_seed(7);
+            let mut rest = bytes;
+            let mut chunk_size = 0;
+            while !rest.is_empty() {
+                chunk_size = (chunk_size + 5) % 31;
+                let (chunk, next) = rest.split_at(chunk_size.min(rest.len()));
+                hasher.write(chunk);
+                rest = next;
+            }
+            assert_eq!(hasher.finish(), u64::from(expected));
+        }
+        for (input, hash) in TEST_VECTORS {
+            let mut hasher = YoshimitsuHasher::default();
+            hasher.write(input.as_bytes());
+            assert_eq!(hasher.finish32(), *hash);
+        }
+    }
+
+    #[test]
+    fn streaming_with_len() {
+        let text = "The quick brown fox jumps over the lazy dog, Schrödinger's café ☕ \
+                    and a few more words to get past several blocks of 24 bytes.";
+        for len in 0..text.len() {
+            let bytes = &text.as_bytes()[..len];
+            let mut hasher = YoshimitsuHasher::with_seed_and_len(7, len as u32);
+            for chunk in bytes.chunks(5) {
+                hasher.write(chunk);
+            }
+            assert!(hasher.lane_b.is_empty());
+            assert_eq!(hasher.finish32(), fnv32a_yoshimitsu_triad(7, bytes));
+        }
+    }
+
+    #[test]
+    fn build_hasher_in_hashmap() {
+        let mut map = std::collections::HashMap::with_hasher(BuildYoshimitsuHasher::default());
+        for i in 0..1000 {
+            map.insert(i.to_string(), i);
+        }
+        for i in 0..1000 {
+            assert_eq!(map.get(&i.to_string()), Some(&i));
+        }
+    }
+
+    fn hash_is_high_half<H: StringHasher>(hasher: H) {
+        for (input, _) in TEST_VECTORS {
+            let hash64 = hasher.hash64(input.as_bytes());
+            assert_eq!(u64::from(hasher.hash(input.as_bytes())), hash64 >> 32);
+        }
+    }
+
+    #[test]
+    fn hash_is_high_half_of_hash64() {
+        hash_is_high_half(Yoshimitsu);
+        hash_is_high_half(Murmur2);
+        hash_is_high_half(XxHash32);
+        hash_is_high_half(WyHash);
+        hash_is_high_half(SipHash::with_keys(1, 2));
+        // 64 bit hashers use the low half as well
+        assert_ne!(WyHash.hash64(b"blub") as u32, 0);
+        assert_eq!(WyHash.hash64(b"blub"), wyhash(0, b"blub"));
+    }
+}
@@ -0,0 +1,51 @@
+const SEED: u32 = 3_242_157_231;
+const M: u32 = 0x5bd1_e995;
+
+/// MurmurHash2 with the seed used by tantivy's term hashmap.
+#[inline]
+pub fn murmurhash2(bytes: &[u8]) -> u32 {
+    murmurhash2_seeded(SEED, bytes)
+}
+
+/// MurmurHash2 by Austin Appleby, reading words as little endian.
+#[inline]
+pub fn murmurhash2_seeded(seed: u32, bytes: &[u8]) -> u32 {
+    let mut h: u32 = seed ^ bytes.len() as u32;
+
+    let mut blocks = bytes.chunks_exact(4);
+    for block in &mut blocks {
+        let mut k = u32::from_le_bytes([block[0], block[1], block[2], block[3]]);
+        k = k.wrapping_mul(M);
+        k ^= k >> 24;
+        k = k.wrapping_mul(M);
+        h = h.wrapping_mul(M);
+        h ^= k;
+    }
+
+    let rest = blocks.remainder();
+    if !rest.is_empty() {
+        for (pos, byte) in rest.iter().enumerate() {
+            h ^= u32::from(*byte) << (pos * 8);
+        }
+        h = h.wrapping_mul(M);
+    }
+    h ^= h >> 13;
+    h = h.wrapping_mul(M);
+    h ^ (h >> 15)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reference_values() {
+        assert_eq!(murmurhash2(b""), 3_632_506_080);
+        assert_eq!(murmurhash2(b"a"), 455_683_869);
+        assert_eq!(murmurhash2(b"ab"), 2_448_092_234);
+        assert_eq!(murmurhash2(b"abc"), 2_066_295_634);
+        assert_eq!(murmurhash2(b"abcd"), 2_588_571_162);
+        assert_eq!(murmurhash2(b"abcde"), 2_988_696_942);
+        assert_eq!(murmurhash2(b"abcdefghijklmnop"), 2_350_868_870);
+    }
+}
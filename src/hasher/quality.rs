@@ -0,0 +1,210 @@
+//! Distribution and avalanche tests for the hashers, a small subset of SMHasher.
+//!
+//! `cargo test --lib quality_report -- --ignored --nocapture` prints the numbers for every hasher
+//! and key set.
+
+use super::*;
+use crate::tests::test_words;
+use std::collections::HashSet;
+use std::string::String;
+use std::vec::Vec;
+
+type HashFn = fn(&[u8]) -> u32;
+
+const HASHERS: &[(&str, HashFn)] = &[
+    ("yoshimitsu", fnv32a_yoshimitsu_hasher),
+    ("murmur2", murmurhash2),
+    ("xxhash32", |bytes| xxhash32(0, bytes)),
+    ("wyhash32", |bytes| wyhash32(0, bytes)),
+    ("siphash13", |bytes| SipHash::with_keys(1, 2).hash(bytes)),
+];
+
+/// Number of buckets for the chi-square test.
+const NUM_BUCKETS_LOG2: u32 = 12;
+
+fn tokens() -> Vec<String> {
+    let contents = test_words();
+    let tokens: HashSet<&str> = contents.split_whitespace().collect();
+    tokens.into_iter().map(String::from).collect()
+}
+
+/// Key sets with little entropy per key, which are hard for weak hashes.
+fn key_sets() -> Vec<(&'static str, Vec<String>)> {
+    let mut short = Vec::new();
+    for a in b'a'..=b'z' {
+        for b in b'a'..=b'z' {
+            for c in b'a'..=b'z' {
+                short.push(String::from_utf8(vec![a, b, c]).unwrap());
+            }
+        }
+    }
+    vec![
+        ("1342-0.txt tokens", tokens()),
+        ("3 letters", short),
+        ("numbers", (0..50_000).map(|i| i.to_string()).collect()),
+        (
+            "prefixed ids",
+            (0..50_000).map(|i| format!("key_{:08}", i)).collect(),
+        ),
+        (
+            "paths",
+            (0..50_000)
+                .map(|i| format!("/usr/share/doc/package-{}/index.html", i))
+                .collect(),
+        ),
+    ]
+}
+
+/// Chi-square of the bucket counts, normalized to standard deviations from the expected value.
+///
+/// `shift` selects which bits are used, the maps use the high bits.
+fn chi_square_z(hashes: &[u32], shift: u32) -> f64 {
+    let num_buckets = 1usize << NUM_BUCKETS_LOG2;
+    let mut counts = vec![0u32; num_buckets];
+    for hash in hashes {
+        counts[((hash >> shift) as usize) & (num_buckets - 1)] += 1;
+    }
+    let expected = hashes.len() as f64 / num_buckets as f64;
+    let chi_square: f64 = counts
+        .iter()
+        .map(|count| {
+            let diff = f64::from(*count) - expected;
+            diff * diff / expected
+        })
+        .sum();
+    let degrees = (num_buckets - 1) as f64;
+    (chi_square - degrees) / (2.0 * degrees).sqrt()
+}
+
+fn num_collisions(hashes: &[u32]) -> usize {
+    hashes.len() - hashes.iter().collect::<HashSet<_>>().len()
+}
+
+/// xorshift64*, to get reproducible random inputs without a dependency.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+}
+
+/// Flips every input bit of random keys with `len` bytes and returns the worst bias of any
+/// output bit, i.e. the largest distance of its flip probability from 0.5.
+fn avalanche_bias(hash: HashFn, len: usize) -> f64 {
+    const NUM_KEYS: usize = 2000;
+    let mut rng = Rng(0x9E37_79B9_7F4A_7C15);
+    let mut flips = vec![[0u32; 32]; len * 8];
+    let mut key = vec![0u8; len];
+    for _ in 0..NUM_KEYS {
+        for byte in key.iter_mut() {
+            *byte = rng.next() as u8;
+        }
+        let hash_before = hash(&key);
+        for (input_bit, flips) in flips.iter_mut().enumerate() {
+            key[input_bit / 8] ^= 1 << (input_bit % 8);
+            let diff = hash_before ^ hash(&key);
+            key[input_bit / 8] ^= 1 << (input_bit % 8);
+            for (output_bit, count) in flips.iter_mut().enumerate() {
+                *count += (diff >> output_bit) & 1;
+            }
+        }
+    }
+    flips
+        .iter()
+        .flat_map(|counts| counts.iter())
+        .map(|count| (f64::from(*count) / NUM_KEYS as f64 - 0.5).abs())
+        .fold(0.0, f64::max)
+}
+
+/// Largest chi-square z of the high bits which passes `distribution`.
+const MAX_Z: f64 = 10.0;
+/// Largest avalanche bias which passes `avalanche`.
+const MAX_BIAS: f64 = 0.05;
+/// Key lengths of the avalanche test.
+const AVALANCHE_LENS: &[usize] = &[4, 8, 16, 32];
+
+/// Checks which a hasher fails with the thresholds above, as hasher and key set or `avalanche`.
+/// They are listed under "Hash Functions" in the README. The tests assert that these checks
+/// still fail, so the list is updated when a hash changes.
+const KNOWN_FAILURES: &[(&str, &str)] = &[
+    ("yoshimitsu", "3 letters"),
+    ("yoshimitsu", "paths"),
+    ("yoshimitsu", "avalanche"),
+];
+
+fn assert_known_result(hasher_name: &str, check: &str, passed: bool, details: &str) {
+    let known_failure = KNOWN_FAILURES.contains(&(hasher_name, check));
+    assert_eq!(
+        passed, !known_failure,
+        "{} {} {}, known failure: {}",
+        hasher_name, check, details, known_failure
+    );
+}
+
+#[test]
+fn distribution() {
+    for (name, keys) in key_sets() {
+        for (hasher_name, hash) in HASHERS {
+            let hashes: Vec<u32> = keys.iter().map(|key| hash(key.as_bytes())).collect();
+            // with 1% collisions the table degrades badly
+            let collisions = num_collisions(&hashes);
+            let z = chi_square_z(&hashes, 32 - NUM_BUCKETS_LOG2);
+            assert_known_result(
+                hasher_name,
+                name,
+                collisions < keys.len() / 100 && z < MAX_Z,
+                &format!("collisions {} z {:.2}", collisions, z),
+            );
+        }
+    }
+}
+
+#[test]
+fn avalanche() {
+    for (hasher_name, hash) in HASHERS {
+        let biases = AVALANCHE_LENS
+            .iter()
+            .map(|len| avalanche_bias(*hash, *len))
+            .collect::<Vec<_>>();
+        assert_known_result(
+            hasher_name,
+            "avalanche",
+            biases.iter().all(|bias| *bias < MAX_BIAS),
+            &format!("biases {:?}", biases),
+        );
+    }
+}
+
+#[test]
+#[ignore]
+fn quality_report() {
+    println!("chi-square z of the high and low {} bits", NUM_BUCKETS_LOG2);
+    for (name, keys) in key_sets() {
+        for (hasher_name, hash) in HASHERS {
+            let hashes: Vec<u32> = keys.iter().map(|key| hash(key.as_bytes())).collect();
+            println!(
+                "{:<20} {:<12} high {:>8.2} low {:>8.2} collisions {}",
+                name,
+                hasher_name,
+                chi_square_z(&hashes, 32 - NUM_BUCKETS_LOG2),
+                chi_square_z(&hashes, 0),
+                num_collisions(&hashes)
+            );
+        }
+    }
+    println!("worst avalanche bias, 0.0 is ideal");
+    for (hasher_name, hash) in HASHERS {
+        for len in AVALANCHE_LENS {
+            println!(
+                "{:<12} {:>2} bytes {:.3}",
+                hasher_name,
+                len,
+                avalanche_bias(*hash, *len)
+            );
+        }
+    }
+}
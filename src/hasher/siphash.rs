@@ -0,0 +1,69 @@
+#[inline]
+fn round(v: &mut [u64; 4]) {
+    v[0] = v[0].wrapping_add(v[1]);
+    v[1] = v[1].rotate_left(13);
+    v[1] ^= v[0];
+    v[0] = v[0].rotate_left(32);
+    v[2] = v[2].wrapping_add(v[3]);
+    v[3] = v[3].rotate_left(16);
+    v[3] ^= v[2];
+    v[0] = v[0].wrapping_add(v[3]);
+    v[3] = v[3].rotate_left(21);
+    v[3] ^= v[0];
+    v[2] = v[2].wrapping_add(v[1]);
+    v[1] = v[1].rotate_left(17);
+    v[1] ^= v[2];
+    v[2] = v[2].rotate_left(32);
+}
+
+/// SipHash-1-3 by Jean-Philippe Aumasson and Daniel J. Bernstein, the keyed hash of the std
+/// `HashMap`.
+#[inline]
+pub fn siphash13(k0: u64, k1: u64, bytes: &[u8]) -> u64 {
+    let mut v = [
+        k0 ^ 0x736f_6d65_7073_6575,
+        k1 ^ 0x646f_7261_6e64_6f6d,
+        k0 ^ 0x6c79_6765_6e65_7261,
+        k1 ^ 0x7465_6462_7974_6573,
+    ];
+    let mut words = bytes.chunks_exact(8);
+    for word in &mut words {
+        let mut m = [0; 8];
+        m.copy_from_slice(word);
+        let m = u64::from_le_bytes(m);
+        v[3] ^= m;
+        round(&mut v);
+        v[0] ^= m;
+    }
+    let mut last = (bytes.len() as u64) << 56;
+    for (pos, byte) in words.remainder().iter().enumerate() {
+        last |= u64::from(*byte) << (pos * 8);
+    }
+    v[3] ^= last;
+    round(&mut v);
+    v[0] ^= last;
+
+    v[2] ^= 0xff;
+    round(&mut v);
+    round(&mut v);
+    round(&mut v);
+    v[0] ^ v[1] ^ v[2] ^ v[3]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::Hasher;
+
+    #[test]
+    fn same_as_std() {
+        // `DefaultHasher::new` is SipHash-1-3 with zero keys
+        let text = b"The quick brown fox jumps over the lazy dog";
+        for len in 0..text.len() {
+            let mut hasher = DefaultHasher::new();
+            hasher.write(&text[..len]);
+            assert_eq!(siphash13(0, 0, &text[..len]), hasher.finish());
+        }
+    }
+}
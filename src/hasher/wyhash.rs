@@ -0,0 +1,111 @@
+const SECRET: [u64; 4] = [
+    0xa076_1d64_78bd_642f,
+    0xe703_7ed1_a0b4_28db,
+    0x8ebc_6af0_9c88_c6e3,
+    0x5899_65cc_7537_4cc3,
+];
+
+#[inline]
+fn mum(a: u64, b: u64) -> (u64, u64) {
+    let r = u128::from(a) * u128::from(b);
+    (r as u64, (r >> 64) as u64)
+}
+
+#[inline]
+fn mix(a: u64, b: u64) -> u64 {
+    let (a, b) = mum(a, b);
+    a ^ b
+}
+
+#[inline]
+fn read_u64(bytes: &[u8]) -> u64 {
+    let mut out = [0; 8];
+    out.copy_from_slice(&bytes[..8]);
+    u64::from_le_bytes(out)
+}
+
+#[inline]
+fn read_u32(bytes: &[u8]) -> u64 {
+    u64::from(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+/// wyhash (final version 3) by Wang Yi, with the default secret.
+#[inline]
+pub fn wyhash(seed: u64, bytes: &[u8]) -> u64 {
+    let len = bytes.len();
+    let mut seed = seed ^ SECRET[0];
+    let (a, b) = if len <= 16 {
+        if len >= 4 {
+            let step = (len >> 3) << 2;
+            (
+                (read_u32(bytes) << 32) | read_u32(&bytes[step..]),
+                (read_u32(&bytes[len - 4..]) << 32) | read_u32(&bytes[len - 4 - step..]),
+            )
+        } else if len > 0 {
+            let a = (u64::from(bytes[0]) << 16)
+                | (u64::from(bytes[len >> 1]) << 8)
+                | u64::from(bytes[len - 1]);
+            (a, 0)
+        } else {
+            (0, 0)
+        }
+    } else {
+        let mut p = bytes;
+        if p.len() > 48 {
+            let mut see1 = seed;
+            let mut see2 = seed;
+            while p.len() > 48 {
+                seed = mix(read_u64(p) ^ SECRET[1], read_u64(&p[8..]) ^ seed);
+                see1 = mix(read_u64(&p[16..]) ^ SECRET[2], read_u64(&p[24..]) ^ see1);
+                see2 = mix(read_u64(&p[32..]) ^ SECRET[3], read_u64(&p[40..]) ^ see2);
+                p = &p[48..];
+            }
+            seed ^= see1 ^ see2;
+        }
+        while p.len() > 16 {
+            seed = mix(read_u64(p) ^ SECRET[1], read_u64(&p[8..]) ^ seed);
+            p = &p[16..];
+        }
+        // the last 16 bytes, which may overlap with the bytes already consumed
+        (read_u64(&bytes[len - 16..]), read_u64(&bytes[len - 8..]))
+    };
+    mix(SECRET[1] ^ len as u64, mix(a ^ SECRET[1], b ^ seed))
+}
+
+/// `wyhash` folded to 32 bits.
+#[inline]
+pub fn wyhash32(seed: u64, bytes: &[u8]) -> u32 {
+    let hash = wyhash(seed, bytes);
+    (hash ^ (hash >> 32)) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reference_values() {
+        assert_eq!(wyhash(0, b""), 0x42bc_986d_c5ee_c4d3);
+        assert_eq!(wyhash(1, b"a"), 0x8450_8dc9_03c3_1551);
+        assert_eq!(wyhash(2, b"abc"), 0x0bc5_4887_cfc9_ecb1);
+        assert_eq!(wyhash(3, b"message digest"), 0x6e2f_f329_8208_a67c);
+        assert_eq!(
+            wyhash(4, b"abcdefghijklmnopqrstuvwxyz"),
+            0x9a64_e42e_8971_95b9
+        );
+        assert_eq!(
+            wyhash(
+                5,
+                b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789"
+            ),
+            0x9199_3832_39c3_2554
+        );
+        assert_eq!(
+            wyhash(
+                6,
+                b"12345678901234567890123456789012345678901234567890123456789012345678901234567890"
+            ),
+            0x7c1c_cf6b_ba30_f5a5
+        );
+    }
+}
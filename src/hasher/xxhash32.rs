@@ -0,0 +1,78 @@
+const PRIME_1: u32 = 0x9E37_79B1;
+const PRIME_2: u32 = 0x85EB_CA77;
+const PRIME_3: u32 = 0xC2B2_AE3D;
+const PRIME_4: u32 = 0x27D4_EB2F;
+const PRIME_5: u32 = 0x1656_67B1;
+
+#[inline]
+fn read_u32(bytes: &[u8]) -> u32 {
+    u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+#[inline]
+fn round(acc: u32, input: u32) -> u32 {
+    acc.wrapping_add(input.wrapping_mul(PRIME_2))
+        .rotate_left(13)
+        .wrapping_mul(PRIME_1)
+}
+
+/// xxHash32 by Yann Collet.
+#[inline]
+pub fn xxhash32(seed: u32, bytes: &[u8]) -> u32 {
+    let mut stripes = bytes.chunks_exact(16);
+    let mut h = if bytes.len() >= 16 {
+        let mut v1 = seed.wrapping_add(PRIME_1).wrapping_add(PRIME_2);
+        let mut v2 = seed.wrapping_add(PRIME_2);
+        let mut v3 = seed;
+        let mut v4 = seed.wrapping_sub(PRIME_1);
+        for stripe in &mut stripes {
+            v1 = round(v1, read_u32(&stripe[0..]));
+            v2 = round(v2, read_u32(&stripe[4..]));
+            v3 = round(v3, read_u32(&stripe[8..]));
+            v4 = round(v4, read_u32(&stripe[12..]));
+        }
+        v1.rotate_left(1)
+            .wrapping_add(v2.rotate_left(7))
+            .wrapping_add(v3.rotate_left(12))
+            .wrapping_add(v4.rotate_left(18))
+    } else {
+        seed.wrapping_add(PRIME_5)
+    };
+    h = h.wrapping_add(bytes.len() as u32);
+
+    let mut words = stripes.remainder().chunks_exact(4);
+    for word in &mut words {
+        h = h
+            .wrapping_add(read_u32(word).wrapping_mul(PRIME_3))
+            .rotate_left(17)
+            .wrapping_mul(PRIME_4);
+    }
+    for byte in words.remainder() {
+        h = h
+            .wrapping_add(u32::from(*byte).wrapping_mul(PRIME_5))
+            .rotate_left(11)
+            .wrapping_mul(PRIME_1);
+    }
+
+    h ^= h >> 15;
+    h = h.wrapping_mul(PRIME_2);
+    h ^= h >> 13;
+    h = h.wrapping_mul(PRIME_3);
+    h ^ (h >> 16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reference_values() {
+        assert_eq!(xxhash32(0, b""), 0x02CC_5D05);
+        assert_eq!(xxhash32(0, b"a"), 0x550D_7456);
+        assert_eq!(xxhash32(0, b"abc"), 0x32D1_53FF);
+        assert_eq!(
+            xxhash32(0, b"Nobody inspects the spammish repetition"),
+            0xE229_3B2F
+        );
+    }
+}
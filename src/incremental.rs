@@ -0,0 +1,248 @@
+//! Incremental resizing of a `StringHashMap`, see `StringHashMap::set_incremental_resize`.
+
+use crate::hasher::StringHasher;
+use crate::pool::private::KeySlot;
+use crate::{Allocator, HashedKey, KeyStorage, QuadraticProbing, StringHashMap, TableEntry};
+use allocator_api2::vec::Vec;
+use core::fmt::Debug;
+
+/// Number of buckets of the old table which are migrated per insert.
+///
+/// The new table is resized again after at least `old_len * 2 / 3` inserts, so any value above
+/// 1.5 finishes the migration in time.
+pub(crate) const MIGRATE_BUCKETS: usize = 16;
+
+/// The table before a resize, whose entries are moved to the new table bit by bit.
+#[derive(Debug)]
+pub(crate) struct OldTable<T, K, A: Allocator + Clone> {
+    pub(crate) table: Vec<TableEntry<T, K>, A>,
+    /// next bucket to migrate
+    pos: usize,
+}
+
+impl<T, K: KeySlot, A: Allocator + Clone> OldTable<T, K, A> {
+    /// Entries which have not been moved to the new table yet, including empty buckets.
+    #[inline]
+    pub(crate) fn entries(&self) -> impl Iterator<Item = &TableEntry<T, K>> {
+        self.table[self.pos..]
+            .iter()
+            .filter(|entry| !entry.pointer.is_moved())
+    }
+
+    /// Buckets and entries which have not been moved to the new table yet.
+    #[inline]
+    pub(crate) fn live_entries(&self) -> impl Iterator<Item = (usize, &TableEntry<T, K>)> {
+        self.table
+            .iter()
+            .enumerate()
+            .skip(self.pos)
+            .filter(|(_, entry)| !entry.pointer.is_null() && !entry.pointer.is_moved())
+    }
+
+    #[inline]
+    pub(crate) fn entries_mut(&mut self) -> impl Iterator<Item = &mut TableEntry<T, K>> {
+        self.table[self.pos..]
+            .iter_mut()
+            .filter(|entry| !entry.pointer.is_moved())
+    }
+
+    #[inline]
+    pub(crate) fn capacity(&self) -> usize {
+        self.table.capacity()
+    }
+}
+
+impl<T: Default + Clone + Debug, A: Allocator + Clone, H: StringHasher, S: KeyStorage>
+    StringHashMap<T, A, H, S>
+{
+    /// Enables or disables incremental resizing.
+    ///
+    /// By default the table is doubled in one go, which rehashes every key and makes a single
+    /// insert take as long as all keys need to be moved. With incremental resizing the old table
+    /// is kept after a resize, and every insert moves a bounded number of its buckets to the new
+    /// table. Lookups check both tables until all entries are moved, which makes them a little
+    /// slower for keys which are not in the map.
+    ///
+    /// A resize still allocates and initializes the new table at once.
+    ///
+    /// # Examples
+    /// ```
+    /// use inohashmap::StringHashMap;
+    /// let mut hashmap = StringHashMap::<u32>::with_power_of_two_size(4);
+    /// hashmap.set_incremental_resize(true);
+    /// for i in 0..100 {
+    ///     hashmap.get_or_create(&i.to_string(), i);
+    /// }
+    /// assert_eq!(hashmap.get("50"), Some(&50));
+    /// hashmap.finish_resize();
+    /// assert!(!hashmap.is_resizing());
+    /// ```
+    #[inline]
+    pub fn set_incremental_resize(&mut self, incremental: bool) {
+        self.incremental_resize = incremental;
+    }
+
+    /// Returns true if entries of an incremental resize are not yet moved to the new table.
+    #[inline]
+    pub fn is_resizing(&self) -> bool {
+        self.old_table.is_some()
+    }
+
+    /// Moves all remaining entries of an incremental resize to the new table.
+    pub fn finish_resize(&mut self) {
+        while self.old_table.is_some() {
+            self.migrate(usize::MAX);
+        }
+    }
+
+    /// Makes `table` the new table and keeps the current one until its entries are moved.
+    #[inline]
+    pub(crate) fn start_incremental_resize(&mut self, mut table: Vec<TableEntry<T, S::Key>, A>) {
+        self.finish_resize();
+        self.mask = table.len() - 1;
+        core::mem::swap(&mut self.table, &mut table);
+        self.bitshift -= 1;
+        self.num_resizes += 1;
+        self.old_table = Some(OldTable { table, pos: 0 });
+    }
+
+    /// Moves the entries of the next `num_buckets` buckets of the old table to the new table.
+    pub(crate) fn migrate(&mut self, num_buckets: usize) {
+        let mut old = match self.old_table.take() {
+            Some(old) => old,
+            None => return,
+        };
+        let end = old.table.len().min(old.pos.saturating_add(num_buckets));
+        for entry in &mut old.table[old.pos..end] {
+            if entry.pointer.is_null() || entry.pointer.is_moved() {
+                continue;
+            }
+            let pointer = core::mem::replace(&mut entry.pointer, S::Key::moved());
+            let value = core::mem::take(&mut entry.value);
+            let hash = self.string_data.hash_key(&pointer, &self.hasher);
+            self.put_entry_resize(hash, TableEntry { value, pointer });
+        }
+        old.pos = end;
+        if old.pos != old.table.len() {
+            self.old_table = Some(old);
+        }
+    }
+
+    /// Returns the bucket in the old table of the first entry with `hash` for which `matches`
+    /// returns true.
+    #[inline]
+    pub(crate) fn find_in_old(
+        &self,
+        hash: u64,
+        mut matches: impl FnMut(&S::Key) -> bool,
+    ) -> Option<usize> {
+        let old = self.old_table.as_ref()?;
+        // the old table has half the size of the new one
+        let mut probe =
+            QuadraticProbing::compute((hash >> (self.bitshift + 1)) as usize, old.table.len() - 1);
+        // small tables can be completely full, the probe sequence visits every bucket within twice
+        // the table length
+        for _ in 0..2 * old.table.len() {
+            let bucket = probe.next_probe();
+            let pointer = &old.table[bucket].pointer;
+            if pointer.is_null() {
+                return None;
+            } else if !pointer.is_moved() && matches(pointer) {
+                return Some(bucket);
+            }
+        }
+        None
+    }
+
+    #[inline]
+    pub(crate) fn get_old_entry(&self, bucket: usize) -> &TableEntry<T, S::Key> {
+        &self.old_table.as_ref().unwrap().table[bucket]
+    }
+
+    /// Like `find_in_old`, but compares the keys with `el`.
+    #[inline]
+    pub(crate) fn find_key_in_old(&self, el: HashedKey<H>) -> Option<usize> {
+        let string_data = &self.string_data;
+        self.find_in_old(el.hash64(), |pointer| {
+            string_data.key_equals(pointer, el.key())
+        })
+    }
+
+    /// Moves the entry in `old_bucket` of the old table to the empty `new_bucket` of the new
+    /// table, so it can be handed out mutably.
+    #[inline]
+    pub(crate) fn move_from_old(&mut self, old_bucket: usize, new_bucket: usize) {
+        let old = self.old_table.as_mut().unwrap();
+        let entry = &mut old.table[old_bucket];
+        let pointer = core::mem::replace(&mut entry.pointer, S::Key::moved());
+        let value = core::mem::take(&mut entry.value);
+        *self.get_entry_mut(new_bucket) = TableEntry { value, pointer };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::StringHashMap;
+    use std::vec::Vec;
+
+    fn keys() -> Vec<String> {
+        (0..2000).map(|i| format!("blub{}", i)).collect()
+    }
+
+    #[test]
+    fn incremental_resize() {
+        let mut hashmap = StringHashMap::<u32>::with_power_of_two_size(4);
+        hashmap.set_incremental_resize(true);
+        let keys = keys();
+        let mut was_resizing = false;
+        for (i, key) in keys.iter().enumerate() {
+            assert_eq!(hashmap.get(key), None);
+            *hashmap.get_or_create(key, i as u32) += 1;
+            was_resizing |= hashmap.is_resizing();
+            // all keys are reachable while entries are moved
+            assert_eq!(hashmap.get(&keys[i / 2]), Some(&(i as u32 / 2 + 1)));
+        }
+        assert!(was_resizing);
+        assert_eq!(hashmap.len(), 2000);
+        assert_eq!(hashmap.values().count(), 2000);
+        assert_eq!(hashmap.iter().count(), 2000);
+        for (i, key) in keys.iter().enumerate() {
+            assert_eq!(hashmap.get(key), Some(&(i as u32 + 1)));
+        }
+        hashmap.finish_resize();
+        assert!(!hashmap.is_resizing());
+        for (i, key) in keys.iter().enumerate() {
+            assert_eq!(hashmap.get(key), Some(&(i as u32 + 1)));
+        }
+    }
+
+    #[test]
+    fn access_while_resizing() {
+        let mut hashmap = StringHashMap::<u32>::with_power_of_two_size(8);
+        hashmap.set_incremental_resize(true);
+        let keys = keys();
+        for key in &keys[..180] {
+            hashmap.get_or_create(key, 1);
+        }
+        assert!(hashmap.is_resizing());
+
+        // values are moved to the new table when they are accessed mutably
+        *hashmap.get_mut("blub0").unwrap() += 1;
+        *hashmap.entry("blub1").or_insert(0) += 1;
+        hashmap.entry("blub2").and_modify(|value| *value += 1);
+        *hashmap.get_or_create("blub3", 0) += 1;
+        for (i, key) in keys[..180].iter().enumerate() {
+            let expected = if i < 4 { 2 } else { 1 };
+            assert_eq!(hashmap.get(key), Some(&expected));
+        }
+        assert_eq!(hashmap.values().sum::<u32>(), 184);
+        for value in hashmap.values_mut() {
+            *value += 1;
+        }
+        assert_eq!(
+            hashmap.iter_mut().map(|(_, value)| *value).sum::<u32>(),
+            364
+        );
+        assert_eq!(hashmap.len(), 180);
+    }
+}
@@ -0,0 +1,346 @@
+/*! Short keys stored inline in the table.
+
+`InlineKeyStorage` stores keys with up to `N` bytes directly in the table entry, instead of a
+`BytesRef` pointing into the string data. Lookups for short keys are resolved with a single
+access into the table. Longer keys spill to the string data like in `StringHashMap`.
+`InlineStringHashMap` is a `StringHashMap` which stores its keys in an `InlineKeyStorage`.
+
+Since short keys are not part of the string data, the storage keeps a copy of every key in
+insertion order for `keys`. It takes about the space the keys would take in the string data.
+
+# Examples
+```
+use inohashmap::InlineStringHashMap;
+let mut hashmap = InlineStringHashMap::<u32>::with_inline_keys();
+*hashmap.get_or_create("short", 0) += 1;
+*hashmap.get_or_create("a rather long key", 0) += 1;
+assert_eq!(hashmap.get("short"), Some(&1));
+assert_eq!(hashmap.get("a rather long key"), Some(&1));
+assert_eq!(
+    hashmap.keys().collect::<Vec<_>>(),
+    &["short", "a rather long key"]
+);
+```
+
+*/
+
+use crate::bytesref::BytesRef;
+use crate::hasher::{StringHasher, Yoshimitsu};
+use crate::pool::private::{ContiguousStorage, KeySlot, Storage};
+use crate::vint::encode_varint_into;
+use crate::{read_string, read_string_checked, Global, MemUsage, StringHashMap, TryReserveError};
+use allocator_api2::vec::Vec;
+use core::convert::TryFrom;
+use core::fmt::Debug;
+
+/// Marks an empty bucket.
+const EMPTY: u8 = u8::MAX;
+/// Marks a key stored in the string data, the `BytesRef` is stored in the first four bytes.
+const SPILLED: u8 = u8::MAX - 1;
+/// Marks a bucket of the old table of an incremental resize, whose entry has been moved.
+const MOVED: u8 = u8::MAX - 2;
+
+/// Default number of bytes stored inline. With a `u32` value this results in 16 byte entries.
+pub const DEFAULT_INLINE_LEN: usize = 11;
+
+/// `StringHashMap` with short keys in the table, see the module documentation.
+pub type InlineStringHashMap<T, const N: usize = DEFAULT_INLINE_LEN> =
+    StringHashMap<T, Global, Yoshimitsu, InlineKeyStorage<N>>;
+
+/// Key storage which keeps keys with up to `N` bytes in the table entries, see the module
+/// documentation.
+#[derive(Debug)]
+pub struct InlineKeyStorage<const N: usize = DEFAULT_INLINE_LEN> {
+    /// contains the keys longer than `N` in compressed format
+    string_data: Vec<u8>,
+    /// every key in insertion order
+    order: Vec<InlineKey<N>>,
+    /// sum of the lengths of the keys in the string data
+    spilled_bytes: usize,
+}
+
+/// Either the key itself, or a `BytesRef` to the key in the string data.
+#[derive(Debug, Clone, Copy)]
+pub struct InlineKey<const N: usize> {
+    /// length of an inline key, `EMPTY`, `SPILLED` or `MOVED`
+    len: u8,
+    bytes: [u8; N],
+}
+
+impl<const N: usize> Default for InlineKey<N> {
+    #[inline]
+    fn default() -> Self {
+        InlineKey {
+            len: EMPTY,
+            bytes: [0; N],
+        }
+    }
+}
+
+impl<const N: usize> KeySlot for InlineKey<N> {
+    #[inline]
+    fn is_null(&self) -> bool {
+        self.len == EMPTY
+    }
+
+    #[inline]
+    fn moved() -> Self {
+        InlineKey {
+            len: MOVED,
+            bytes: [0; N],
+        }
+    }
+
+    #[inline]
+    fn is_moved(&self) -> bool {
+        self.len == MOVED
+    }
+}
+
+impl<const N: usize> InlineKey<N> {
+    #[inline]
+    fn pointer(&self) -> BytesRef {
+        BytesRef(u32::from_le_bytes([
+            self.bytes[0],
+            self.bytes[1],
+            self.bytes[2],
+            self.bytes[3],
+        ]))
+    }
+
+    #[inline]
+    fn as_str<'a>(&'a self, string_data: &'a [u8]) -> &'a str {
+        if self.len == SPILLED {
+            read_string(string_data, self.pointer())
+        } else {
+            // inline keys are copied from a `&str`
+            unsafe { core::str::from_utf8_unchecked(&self.bytes[..self.len as usize]) }
+        }
+    }
+}
+
+impl<T: Default + Clone + Debug, const N: usize> InlineStringHashMap<T, N> {
+    /// Creates a map which stores its keys in a new `InlineKeyStorage`.
+    #[inline]
+    pub fn with_inline_keys() -> Self {
+        Self::with_power_of_two_size_and_inline_keys(10)
+    }
+
+    #[inline]
+    pub fn with_power_of_two_size_and_inline_keys(power_of_two: usize) -> Self {
+        Self::with_power_of_two_size_and_pool(power_of_two, InlineKeyStorage::new())
+    }
+
+    /// Returns the keys in insertion order.
+    #[inline]
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        let string_data = &self.string_data.string_data;
+        self.string_data
+            .order
+            .iter()
+            .map(move |key| key.as_str(string_data))
+    }
+}
+
+impl<const N: usize> Default for InlineKeyStorage<N> {
+    fn default() -> Self {
+        InlineKeyStorage::new()
+    }
+}
+
+impl<const N: usize> InlineKeyStorage<N> {
+    #[inline]
+    pub fn new() -> Self {
+        assert!(
+            (4..MOVED as usize).contains(&N),
+            "inline length needs to fit a BytesRef and the length markers"
+        );
+        InlineKeyStorage {
+            string_data: Vec::new(),
+            order: Vec::new(),
+            spilled_bytes: 0,
+        }
+    }
+}
+
+impl<const N: usize> Storage for InlineKeyStorage<N> {
+    type Key = InlineKey<N>;
+
+    #[inline]
+    fn insert(&mut self, key: &str) -> InlineKey<N> {
+        let mut bytes = [0; N];
+        let key = if key.len() <= N {
+            bytes[..key.len()].copy_from_slice(key.as_bytes());
+            InlineKey {
+                len: key.len() as u8,
+                bytes,
+            }
+        } else {
+            let pos =
+                BytesRef(u32::try_from(self.string_data.len()).expect("string data exceeds 4GB"));
+            encode_varint_into(
+                &mut self.string_data,
+                u32::try_from(key.len()).expect("key is longer than 4GB"),
+            );
+            self.string_data.extend_from_slice(key.as_bytes());
+            self.spilled_bytes += key.len();
+            bytes[..4].copy_from_slice(&pos.addr().to_le_bytes());
+            InlineKey {
+                len: SPILLED,
+                bytes,
+            }
+        };
+        self.order.push(key);
+        key
+    }
+
+    #[inline]
+    fn key_equals(&self, pointer: &InlineKey<N>, key: &str) -> bool {
+        if key.len() <= N {
+            pointer.len as usize == key.len() && &pointer.bytes[..key.len()] == key.as_bytes()
+        } else {
+            pointer.len == SPILLED && read_string(&self.string_data, pointer.pointer()) == key
+        }
+    }
+
+    #[inline]
+    fn hash_key<H: StringHasher>(&self, pointer: &InlineKey<N>, hasher: &H) -> u64 {
+        hasher.hash64(pointer.as_str(&self.string_data).as_bytes())
+    }
+
+    #[inline]
+    fn key_ptr(&self, pointer: &InlineKey<N>) -> *const u8 {
+        if pointer.len == SPILLED {
+            self.string_data
+                .as_ptr()
+                .wrapping_add(pointer.pointer().addr() as usize)
+        } else {
+            pointer.bytes.as_ptr()
+        }
+    }
+
+    #[inline]
+    fn try_reserve(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        self.order.try_reserve(1)?;
+        self.string_data.try_reserve(additional)
+    }
+
+    #[inline]
+    fn shrink_to_fit(&mut self) {
+        self.string_data.shrink_to_fit();
+        self.order.shrink_to_fit();
+    }
+
+    /// The keys in insertion order are accounted as string data.
+    #[inline]
+    fn mem_usage(&self, _key_bytes: usize) -> MemUsage {
+        let key_size = core::mem::size_of::<InlineKey<N>>();
+        MemUsage {
+            string_data: self.string_data.capacity() + self.order.capacity() * key_size,
+            string_data_unused: self.string_data.capacity() - self.string_data.len()
+                + (self.order.capacity() - self.order.len()) * key_size,
+            varint_overhead: self.string_data.len() - self.spilled_bytes,
+            ..MemUsage::default()
+        }
+    }
+}
+
+impl<const N: usize> ContiguousStorage for InlineKeyStorage<N> {
+    #[inline]
+    fn read<'a>(&'a self, pointer: &'a InlineKey<N>) -> &'a str {
+        pointer.as_str(&self.string_data)
+    }
+
+    #[inline]
+    fn read_checked<'a>(&'a self, pointer: &'a InlineKey<N>) -> Option<&'a str> {
+        if pointer.len == SPILLED {
+            read_string_checked(&self.string_data, pointer.pointer())
+        } else {
+            let bytes = pointer.bytes.get(..pointer.len as usize)?;
+            core::str::from_utf8(bytes).ok()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{assert_same_values, count_words, test_words};
+    use crate::TableEntry;
+    use std::vec::Vec;
+
+    #[test]
+    fn entry_size() {
+        assert_eq!(std::mem::size_of::<TableEntry<u32, InlineKey<11>>>(), 16);
+        assert_eq!(std::mem::size_of::<TableEntry<u32, InlineKey<7>>>(), 12);
+    }
+
+    #[test]
+    fn short_and_long_keys() {
+        let mut hashmap = InlineStringHashMap::<u32, 4>::with_power_of_two_size_and_inline_keys(1);
+        hashmap.get_or_create("blub1", 4);
+        hashmap.get_or_create("", 1);
+        hashmap.get_or_create("blu", 2);
+        hashmap.get_or_create("blub12345", 5);
+        hashmap.get_or_create("blub", 3);
+
+        assert_eq!(hashmap.len(), 5);
+        assert_eq!(hashmap.get(""), Some(&1));
+        assert_eq!(hashmap.get("blu"), Some(&2));
+        assert_eq!(hashmap.get("blub"), Some(&3));
+        assert_eq!(hashmap.get("blub1"), Some(&4));
+        assert_eq!(hashmap.get_mut("blub12345"), Some(&mut 5));
+        assert_eq!(hashmap.get("blub2"), None);
+        assert_eq!(hashmap.get("bl"), None);
+        assert_eq!(hashmap.get_or_create("blub1", 0), &4);
+
+        let mut entries = hashmap.iter().collect::<Vec<_>>();
+        entries.sort();
+        assert_eq!(
+            entries,
+            &[
+                ("", &1),
+                ("blu", &2),
+                ("blub", &3),
+                ("blub1", &4),
+                ("blub12345", &5)
+            ]
+        );
+        assert_eq!(
+            hashmap.keys().collect::<Vec<_>>(),
+            &["blub1", "", "blu", "blub12345", "blub"]
+        );
+        for (_, value) in hashmap.iter_mut() {
+            *value += 1;
+        }
+        assert_eq!(hashmap.values().sum::<u32>(), 20);
+        assert_eq!(hashmap.entry("blub12345").key(), "blub12345");
+        // only the long keys are in the string data
+        assert_eq!(hashmap.string_data.string_data.len(), 6 + 10);
+        assert_eq!(hashmap.mem_usage().varint_overhead, 2);
+    }
+
+    #[test]
+    fn read_checked() {
+        let mut storage = InlineKeyStorage::<4>::new();
+        let short = storage.insert("blub");
+        let long = storage.insert("blub1");
+        assert_eq!(storage.read_checked(&short), Some("blub"));
+        assert_eq!(storage.read_checked(&long), Some("blub1"));
+        assert_eq!(storage.read_checked(&InlineKey::moved()), None);
+    }
+
+    #[test]
+    fn same_as_stringhashmap() {
+        let contents = test_words();
+        let mut map = InlineStringHashMap::<u32>::with_inline_keys();
+        map.set_incremental_resize(true);
+        for text in contents.split_whitespace() {
+            *map.get_or_create(text, 0) += 1;
+        }
+        let reference = count_words(&contents);
+        assert_same_values(&reference, map.len(), |key| map.get(key).copied());
+        assert!(map.keys().eq(reference.keys()));
+        assert_eq!(map.stats().len, reference.len());
+    }
+}
@@ -0,0 +1,249 @@
+/*! String interner built on `StringHashMap`.
+
+A `Symbol` is the offset of the string in the string data, so resolving a symbol is a single read
+without any additional lookup table.
+
+# Examples
+```
+use inohashmap::Interner;
+let mut interner = Interner::new();
+let blub = interner.intern("blub");
+assert_eq!(interner.intern("blub"), blub);
+assert_eq!(interner.get("blub"), Some(blub));
+assert_eq!(interner.resolve(blub), "blub");
+```
+
+*/
+
+use crate::bytesref::BytesRef;
+use crate::vint::decode_varint_slice;
+use crate::{read_string, read_string_checked, StringHashMap};
+use core::num::NonZeroU32;
+
+/// Handle for an interned string. 4 bytes, and 4 bytes as `Option<Symbol>`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Symbol(NonZeroU32);
+
+impl Symbol {
+    #[inline]
+    fn from_pointer(pointer: BytesRef) -> Symbol {
+        // the null pointer is `u32::MAX`, so every valid pointer fits
+        Symbol(NonZeroU32::new(pointer.addr() + 1).unwrap())
+    }
+
+    #[inline]
+    fn pointer(self) -> BytesRef {
+        BytesRef(self.0.get() - 1)
+    }
+
+    /// Returns the symbol as `u32`, e.g. to store it in a compact format.
+    #[inline]
+    pub fn as_u32(self) -> u32 {
+        self.0.get()
+    }
+
+    /// Creates a symbol from the value returned by `as_u32`.
+    #[inline]
+    pub fn from_u32(value: u32) -> Option<Symbol> {
+        NonZeroU32::new(value).map(Symbol)
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Interner {
+    map: StringHashMap<()>,
+    /// A string is longer than 127 bytes, so its varint length isn't a single ASCII byte and the
+    /// string data is no longer valid UTF-8 as a whole.
+    long_strings: bool,
+}
+
+impl Interner {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline]
+    pub fn with_power_of_two_size(power_of_two: usize) -> Self {
+        Interner {
+            map: StringHashMap::with_power_of_two_size(power_of_two),
+            long_strings: false,
+        }
+    }
+
+    /// Returns the symbol of `el`, inserting it if necessary.
+    #[inline]
+    pub fn intern(&mut self, el: &str) -> Symbol {
+        self.long_strings |= el.len() > 127;
+        Symbol::from_pointer(
+            self.map
+                .get_or_create_entry(self.map.hash_key(el), ())
+                .pointer,
+        )
+    }
+
+    /// Returns the symbol of `el`, if it has been interned.
+    #[inline]
+    pub fn get(&self, el: &str) -> Option<Symbol> {
+        self.map
+            .find_entry(self.map.hash_key(el))
+            .map(|entry| Symbol::from_pointer(entry.pointer))
+    }
+
+    /// Returns the string of `symbol`.
+    ///
+    /// The string isn't validated as UTF-8, unless a string longer than 127 bytes has been
+    /// interned.
+    ///
+    /// # Panics
+    /// Panics if `symbol` doesn't point to the start of a string of this interner. A symbol of a
+    /// different interner may also point to an unrelated string, which is returned.
+    #[inline]
+    pub fn resolve(&self, symbol: Symbol) -> &str {
+        let text = if self.long_strings {
+            read_string_checked(&self.map.string_data, symbol.pointer())
+        } else {
+            read_string_in_utf8(&self.map.string_data, symbol.pointer())
+        };
+        text.expect("symbol from a different interner")
+    }
+
+    /// Returns the string of `symbol` without checking that it belongs to this interner.
+    ///
+    /// # Safety
+    /// `symbol` has to be returned by this interner.
+    #[inline]
+    pub unsafe fn resolve_unchecked(&self, symbol: Symbol) -> &str {
+        read_string(&self.map.string_data, symbol.pointer())
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    #[inline]
+    pub fn shrink_to_fit(&mut self) {
+        self.map.shrink_to_fit();
+    }
+
+    /// Returns all symbols with their strings in insertion order.
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = (Symbol, &str)> {
+        let mut keys = self.map.keys();
+        core::iter::from_fn(move || {
+            let pointer = BytesRef(keys.pos as u32);
+            keys.next()
+                .map(|text| (Symbol::from_pointer(pointer), text))
+        })
+    }
+}
+
+/// Like `read_string_checked` for string data which is valid UTF-8 as a whole.
+///
+/// Every slice of valid UTF-8 which starts and ends on a char boundary is valid UTF-8, so checking
+/// both ends is enough, wherever `pos` points to.
+#[inline]
+fn read_string_in_utf8(string_data: &[u8], pos: BytesRef) -> Option<&str> {
+    let mut pos = pos.addr() as usize;
+    let length_string = decode_varint_slice(string_data, &mut pos)?;
+    let end = pos.checked_add(length_string as usize)?;
+    let bytes = string_data.get(pos..end)?;
+    if is_char_boundary(string_data, pos) && is_char_boundary(string_data, end) {
+        Some(unsafe { core::str::from_utf8_unchecked(bytes) })
+    } else {
+        None
+    }
+}
+
+/// Like `str::is_char_boundary`, the end of the data is a boundary.
+#[inline]
+fn is_char_boundary(data: &[u8], index: usize) -> bool {
+    // continuation bytes are 0b10xx_xxxx
+    data.get(index).is_none_or(|&byte| (byte as i8) >= -0x40)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::vec::Vec;
+
+    #[test]
+    fn intern_and_resolve() {
+        let mut interner = Interner::with_power_of_two_size(1);
+        let symbols = (0..100)
+            .map(|i| interner.intern(&format!("blub{}", i)))
+            .collect::<Vec<_>>();
+        assert_eq!(interner.len(), 100);
+        for (i, symbol) in symbols.iter().enumerate() {
+            assert_eq!(interner.intern(&format!("blub{}", i)), *symbol);
+            assert_eq!(interner.get(&format!("blub{}", i)), Some(*symbol));
+            assert_eq!(interner.resolve(*symbol), format!("blub{}", i));
+            assert_eq!(Symbol::from_u32(symbol.as_u32()), Some(*symbol));
+        }
+        assert_eq!(interner.get("blub100"), None);
+        assert_eq!(
+            interner
+                .iter()
+                .map(|(symbol, _)| symbol)
+                .collect::<Vec<_>>(),
+            symbols
+        );
+        assert_eq!(interner.iter().nth(3).unwrap().1, "blub3");
+        assert_eq!(core::mem::size_of::<Option<Symbol>>(), 4);
+    }
+
+    #[test]
+    fn empty_string() {
+        let mut interner = Interner::new();
+        assert!(interner.is_empty());
+        let empty = interner.intern("");
+        let blub = interner.intern("blub");
+        assert_ne!(empty, blub);
+        assert_eq!(interner.resolve(empty), "");
+        assert_eq!(interner.resolve(blub), "blub");
+    }
+
+    #[test]
+    #[should_panic]
+    fn resolve_foreign_symbol() {
+        let mut interner = Interner::new();
+        interner.intern("blub");
+        let mut other = Interner::new();
+        other.intern("blub1");
+        let symbol = other.intern("blub2");
+        interner.resolve(symbol);
+    }
+
+    #[test]
+    fn resolve_any_offset() {
+        let mut interner = Interner::new();
+        for key in ["blub", "", "äöü", "日本語", "𝄞 clef", "blub2"].iter() {
+            interner.intern(key);
+        }
+        assert!(!interner.long_strings);
+        let string_data = &interner.map.string_data;
+        // every offset either fails or reads the same string as the validating read
+        for pos in 0..string_data.len() as u32 + 2 {
+            if let Some(text) = read_string_in_utf8(string_data, BytesRef(pos)) {
+                assert_eq!(read_string_checked(string_data, BytesRef(pos)), Some(text));
+            }
+        }
+    }
+
+    #[test]
+    fn resolve_long_string() {
+        let mut interner = Interner::new();
+        let blub = interner.intern("blub");
+        let long = "ä".repeat(100);
+        let long_symbol = interner.intern(&long);
+        assert!(interner.long_strings);
+        assert_eq!(interner.resolve(blub), "blub");
+        assert_eq!(interner.resolve(long_symbol), long);
+    }
+}
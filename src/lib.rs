@@ -18,22 +18,92 @@ assert_eq!(*val, 2);
 
 */
 
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+#![cfg_attr(feature = "nightly", feature(allocator_api))]
+
+extern crate alloc;
+
 use crate::bytesref::BytesRef;
-use crate::hasher::fnv32a_yoshimitsu_hasher;
+use crate::hasher::{StringHasher, Yoshimitsu};
+use crate::incremental::OldTable;
+use crate::pool::private::KeySlot;
+use crate::vint::decode_varint_slice;
+use allocator_api2::vec::Vec;
 use core::fmt::Debug;
-use vint32::{decode_varint_slice, encode_varint_into};
+pub mod agg;
+mod batch;
+mod blob;
 mod bytesref;
+mod composite;
+mod dense;
+mod entry;
+mod handle;
+mod hashed_key;
 pub mod hasher;
+mod incremental;
+mod inline;
+mod interner;
+mod mem_usage;
+mod multimap;
+mod namespaced;
+mod pool;
+mod prefix;
+mod reseed;
+mod stats;
+mod topk;
+mod vint;
 
+pub use crate::agg::{Aggregator, StringAggMap};
+pub use crate::blob::StringBlobMap;
+pub use crate::composite::{CompositeStringHashMap, Parts, PartsIter};
+pub use crate::dense::DenseStringHashMap;
+pub use crate::entry::{Entry, OccupiedEntry, VacantEntry};
+pub use crate::handle::KeyHandle;
+pub use crate::hashed_key::{HashedKey, IntoHashedKey};
+pub use crate::inline::{InlineKeyStorage, InlineStringHashMap, DEFAULT_INLINE_LEN};
+pub use crate::interner::{Interner, Symbol};
+pub use crate::mem_usage::{HeapSize, MemUsage};
+pub use crate::multimap::StringMultiMap;
+pub use crate::namespaced::NamespacedStringHashMap;
+pub use crate::pool::{ContiguousKeyStorage, KeyStorage, StringPool};
+pub use crate::prefix::{PrefixKeyStorage, PrefixStringHashMap};
+pub use crate::stats::Stats;
+pub use crate::topk::{HeavyHitter, HeavyHitters};
+pub use allocator_api2::alloc::{AllocError, Allocator, Global};
+pub use allocator_api2::collections::TryReserveError;
+
+/// Table and string data are allocated with `A`, which defaults to the global allocator.
+///
+/// Keys are hashed with `H`, which defaults to `fnv32a_yoshimitsu_hasher`. Other hashers are in
+/// the `hasher` module.
+///
+/// Keys are stored in `S`, which defaults to string data owned by the map. A `StringPool` can be
+/// shared by several maps instead, see `with_pool`.
 #[derive(Debug)]
-pub struct StringHashMap<T> {
+pub struct StringHashMap<
+    T,
+    A: Allocator + Clone = Global,
+    H: StringHasher = Yoshimitsu,
+    S: KeyStorage = Vec<u8, A>,
+> {
     /// contains string in compressed format
-    pub(crate) string_data: Vec<u8>,
+    pub(crate) string_data: S,
     /// pointer to string data and value
-    pub(crate) table: Vec<TableEntry<T>>,
+    pub(crate) table: Vec<TableEntry<T, S::Key>, A>,
     bitshift: usize,
     pub occupied: usize,
-    mask: u32,
+    mask: usize,
+    /// sum of the lengths of all keys, without the varint length prefix
+    key_bytes: usize,
+    num_resizes: usize,
+    incremental_resize: bool,
+    /// table of an unfinished incremental resize
+    old_table: Option<OldTable<T, S::Key, A>>,
+    hasher: H,
+    max_probe_len: Option<usize>,
+    /// table size at the last reseed
+    reseeded_at: usize,
+    num_reseeds: usize,
 }
 
 impl<T: Default + Clone + Debug> Default for StringHashMap<T> {
@@ -43,28 +113,141 @@ impl<T: Default + Clone + Debug> Default for StringHashMap<T> {
 }
 
 #[derive(Debug, Clone, Copy, Default)]
-pub(crate) struct TableEntry<T> {
-    value: T,
-    pointer: BytesRef,
+pub(crate) struct TableEntry<T, K = BytesRef> {
+    pub(crate) value: T,
+    /// key in the key storage, see `KeySlot`
+    pub(crate) pointer: K,
 }
 
 impl<T: Default + Clone + Debug> StringHashMap<T> {
     #[inline]
     pub fn with_power_of_two_size(power_of_two: usize) -> Self {
+        Self::with_power_of_two_size_in(power_of_two, Global)
+    }
+    #[inline]
+    pub fn new() -> Self {
+        Self::with_power_of_two_size(10)
+    }
+}
+
+impl<T: Default + Clone + Debug, H: StringHasher> StringHashMap<T, Global, H> {
+    /// Creates a map which hashes keys with `hasher`.
+    ///
+    /// # Examples
+    /// ```
+    /// use inohashmap::hasher::XxHash32;
+    /// use inohashmap::StringHashMap;
+    /// let mut hashmap = StringHashMap::<u32, _, _>::with_hasher(XxHash32);
+    /// hashmap.get_or_create("blub", 1);
+    /// assert_eq!(hashmap.get("blub"), Some(&1));
+    /// ```
+    #[inline]
+    pub fn with_hasher(hasher: H) -> Self {
+        Self::with_power_of_two_size_and_hasher(10, hasher)
+    }
+
+    #[inline]
+    pub fn with_power_of_two_size_and_hasher(power_of_two: usize, hasher: H) -> Self {
+        Self::with_power_of_two_size_and_hasher_in(power_of_two, hasher, Global)
+    }
+}
+
+impl<T: Default + Clone + Debug, A: Allocator + Clone> StringHashMap<T, A> {
+    #[inline]
+    pub fn with_power_of_two_size_in(power_of_two: usize, alloc: A) -> Self {
+        Self::with_power_of_two_size_and_hasher_in(power_of_two, Yoshimitsu, alloc)
+    }
+
+    /// Like `with_power_of_two_size_in`, but returns an error instead of aborting when the
+    /// allocator fails.
+    #[inline]
+    pub fn try_with_power_of_two_size_in(
+        power_of_two: usize,
+        alloc: A,
+    ) -> Result<Self, TryReserveError> {
+        let shift = power_of_two - 1;
+        let mut table = Vec::new_in(alloc.clone());
+        table.try_reserve_exact(1 << shift)?;
+        table.resize(1 << shift, TableEntry::default());
+        let mut string_data = Vec::new_in(alloc);
+        string_data.try_reserve((1 << shift) * 2)?;
+        Ok(Self::from_parts(
+            table,
+            string_data,
+            power_of_two,
+            Yoshimitsu,
+        ))
+    }
+
+    #[inline]
+    pub fn new_in(alloc: A) -> Self {
+        Self::with_power_of_two_size_in(10, alloc)
+    }
+}
+
+impl<T: Default + Clone + Debug, A: Allocator + Clone, H: StringHasher> StringHashMap<T, A, H> {
+    #[inline]
+    pub fn with_power_of_two_size_and_hasher_in(power_of_two: usize, hasher: H, alloc: A) -> Self {
         let shift = power_of_two - 1;
-        let mut table = vec![];
+        let mut table = Vec::with_capacity_in(1 << shift, alloc.clone());
         table.resize(1 << shift, TableEntry::default());
+        Self::from_parts(
+            table,
+            Vec::with_capacity_in((1 << shift) * 2, alloc),
+            power_of_two,
+            hasher,
+        )
+    }
+
+    #[inline]
+    pub fn keys(&self) -> KeyIterator<'_, T, A, H> {
+        KeyIterator { map: self, pos: 0 }
+    }
+}
+
+impl<T: Default + Clone + Debug, A: Allocator + Clone, H: StringHasher, S: KeyStorage>
+    StringHashMap<T, A, H, S>
+{
+    #[inline]
+    pub(crate) fn from_parts(
+        table: Vec<TableEntry<T, S::Key>, A>,
+        string_data: S,
+        power_of_two: usize,
+        hasher: H,
+    ) -> Self {
         StringHashMap {
-            string_data: Vec::with_capacity((1 << shift) * 2),
-            mask: table.len() as u32 - 1,
+            string_data,
+            mask: table.len() - 1,
             table,
-            bitshift: 32 - power_of_two,
+            bitshift: 64 - power_of_two,
             occupied: 0,
+            key_bytes: 0,
+            num_resizes: 0,
+            incremental_resize: false,
+            old_table: None,
+            hasher,
+            max_probe_len: None,
+            reseeded_at: 0,
+            num_reseeds: 0,
         }
     }
+
+    /// Returns the hasher of the map.
     #[inline]
-    pub fn new() -> Self {
-        Self::with_power_of_two_size(10)
+    pub fn hasher(&self) -> &H {
+        &self.hasher
+    }
+
+    /// Hashes `key` with the hasher of the map, e.g. to look it up later without hashing again.
+    #[inline]
+    pub fn hash_key<'k>(&self, key: &'k str) -> HashedKey<'k, H> {
+        HashedKey::with_hasher(key, &self.hasher)
+    }
+
+    /// Returns the allocator used for table and string data.
+    #[inline]
+    pub fn allocator(&self) -> &A {
+        self.table.allocator()
     }
 
     #[inline]
@@ -83,73 +266,178 @@ impl<T: Default + Clone + Debug> StringHashMap<T> {
         self.table.shrink_to_fit();
     }
 
+    /// Returns the memory usage of the map. Heap memory owned by the values is not included,
+    /// see `mem_usage_with_heap`.
     #[inline]
-    pub fn get(&mut self, el: &str) -> Option<&T> {
-        let mut probe = self.get_probe(el);
-        let mut hash = probe.next_probe() as usize;
-
-        loop {
-            let entry = self.get_entry(hash);
-            if entry.pointer.is_null() {
-                return None;
-            } else if self.read_string(entry.pointer) == el {
-                return Some(&self.get_entry(hash as usize).value);
-            }
-            hash = probe.next_probe() as usize;
+    pub fn mem_usage(&self) -> MemUsage {
+        MemUsage {
+            table: (self.table.capacity() + self.old_table.as_ref().map_or(0, OldTable::capacity))
+                * core::mem::size_of::<TableEntry<T, S::Key>>(),
+            ..self.string_data.mem_usage(self.key_bytes)
         }
     }
+
+    /// Returns the memory usage of the map, including the heap memory owned by the values.
+    ///
+    /// Unlike `mem_usage` this visits every value.
+    pub fn mem_usage_with_heap(&self) -> MemUsage
+    where
+        T: HeapSize,
+    {
+        let mut usage = self.mem_usage();
+        usage.values_heap = self.values().map(HeapSize::heap_size).sum();
+        usage
+    }
+
+    #[inline]
+    pub fn get<'k>(&self, el: impl IntoHashedKey<'k, H>) -> Option<&T> {
+        self.find_entry(el.into_hashed_key(&self.hasher))
+            .map(|entry| &entry.value)
+    }
+    #[inline]
+    pub fn get_mut<'k>(&mut self, el: impl IntoHashedKey<'k, H>) -> Option<&mut T> {
+        let el = el.into_hashed_key(&self.hasher);
+        self.probe_bucket_mut(el)
+            .ok()
+            .map(move |bucket| &mut self.get_entry_mut(bucket).value)
+    }
+
     #[inline]
-    pub fn get_mut(&mut self, el: &str) -> Option<&mut T> {
-        let mut probe = self.get_probe(el);
-        let mut hash = probe.next_probe() as usize;
+    pub fn get_or_create<'k>(&mut self, el: impl IntoHashedKey<'k, H>, value: T) -> &mut T {
+        let el = el.into_hashed_key(&self.hasher);
+        &mut self.get_or_create_entry(el, value).value
+    }
 
-        loop {
-            let entry = self.get_entry(hash);
-            if entry.pointer.is_null() {
-                return None;
-            } else if self.read_string(entry.pointer) == el {
-                return Some(&mut self.get_entry_mut(hash as usize).value);
+    #[inline]
+    pub(crate) fn get_or_create_entry(
+        &mut self,
+        el: HashedKey<H>,
+        value: T,
+    ) -> &mut TableEntry<T, S::Key> {
+        self.get_or_create_entry_with(el, || value)
+    }
+
+    #[inline]
+    pub(crate) fn get_or_create_entry_with<F: FnOnce() -> T>(
+        &mut self,
+        el: HashedKey<H>,
+        value: F,
+    ) -> &mut TableEntry<T, S::Key> {
+        self.reserve_one();
+        match self.probe_bucket_mut(el) {
+            Ok(bucket) => self.get_entry_mut(bucket),
+            Err(bucket) => {
+                let bucket = self.check_probe_len(el, bucket);
+                self.occupied += 1;
+                self.put_in_bucket(bucket, el.key(), value())
             }
-            hash = probe.next_probe() as usize;
         }
     }
 
+    /// Resizes the table or moves entries of an incremental resize, before a key is inserted.
     #[inline]
-    pub fn get_or_create(&mut self, el: &str, value: T) -> &mut T {
+    pub(crate) fn reserve_one(&mut self) {
         // check load factor, resize when 0.5
         // if self.occupied as f32 * 1.5 > self.table.len() as f32 {
         if self.occupied as f32 * 1.5 > self.table.len() as f32 {
             self.resize();
+        } else if self.old_table.is_some() {
+            self.migrate(incremental::MIGRATE_BUCKETS);
         }
-        let mut probe = self.get_probe(el);
-        let mut hash = probe.next_probe() as usize;
+    }
+
+    /// Like `reserve_one`, but returns an error instead of aborting when the allocator fails.
+    #[inline]
+    pub(crate) fn try_reserve_one(&mut self) -> Result<(), TryReserveError> {
+        if self.occupied as f32 * 1.5 > self.table.len() as f32 {
+            self.try_resize()?;
+        } else if self.old_table.is_some() {
+            self.migrate(incremental::MIGRATE_BUCKETS);
+        }
+        Ok(())
+    }
+
+    #[inline]
+    pub(crate) fn find_entry(&self, el: HashedKey<H>) -> Option<&TableEntry<T, S::Key>> {
+        match self.probe_bucket(el) {
+            Ok(bucket) => Some(self.get_entry(bucket)),
+            Err(_) if self.old_table.is_some() => self
+                .find_key_in_old(el)
+                .map(|bucket| self.get_old_entry(bucket)),
+            Err(_) => None,
+        }
+    }
+
+    /// Like `probe_bucket`, but moves `el` to the new table if it is found in the old table of an
+    /// incremental resize.
+    #[inline]
+    pub(crate) fn probe_bucket_mut(&mut self, el: HashedKey<H>) -> Result<usize, usize> {
+        match self.probe_bucket(el) {
+            Err(bucket) if self.old_table.is_some() => match self.find_key_in_old(el) {
+                Some(old_bucket) => {
+                    self.move_from_old(old_bucket, bucket);
+                    Ok(bucket)
+                }
+                None => Err(bucket),
+            },
+            result => result,
+        }
+    }
+
+    /// Returns `Ok` with the bucket of `el`, or `Err` with the empty bucket where it would be
+    /// inserted.
+    #[inline]
+    pub(crate) fn probe_bucket(&self, el: HashedKey<H>) -> Result<usize, usize> {
+        let mut probe = self.get_probe(el.hash64());
+        let mut hash = probe.next_probe();
 
         loop {
             let entry = self.get_entry(hash);
             if entry.pointer.is_null() {
+                return Err(hash);
+            } else if self.string_data.key_equals(&entry.pointer, el.key()) {
+                return Ok(hash);
+            }
+            hash = probe.next_probe();
+        }
+    }
+
+    /// Like `get_or_create`, but returns an error instead of aborting when the allocator fails.
+    ///
+    /// Capacity for `el` is reserved in the string data even if it is already in the map.
+    #[inline]
+    pub fn try_get_or_create<'k>(
+        &mut self,
+        el: impl IntoHashedKey<'k, H>,
+        value: T,
+    ) -> Result<&mut T, TryReserveError> {
+        let el = el.into_hashed_key(&self.hasher);
+        self.try_reserve_one()?;
+        self.string_data
+            .try_reserve(el.key().len() + vint::MAX_VARINT_LEN)?;
+        match self.probe_bucket_mut(el) {
+            Ok(bucket) => Ok(&mut self.get_entry_mut(bucket).value),
+            Err(bucket) => {
+                let bucket = self.try_check_probe_len(el, bucket)?;
                 self.occupied += 1;
-                let inserted_value = self.put_in_bucket(hash as usize, el, value);
-                return &mut inserted_value.value;
-            } else if self.read_string(entry.pointer) == el {
-                return &mut self.get_entry_mut(hash as usize).value;
+                Ok(&mut self.put_in_bucket(bucket, el.key(), value).value)
             }
-            hash = probe.next_probe() as usize;
         }
     }
 
     #[inline]
-    fn get_probe(&self, el: &str) -> QuadraticProbing {
-        let hash = fnv32a_yoshimitsu_hasher(el.as_bytes());
-        let hash = hash >> self.bitshift;
+    pub(crate) fn get_probe(&self, hash: u64) -> QuadraticProbing {
+        // the high bits select the bucket, they fit into usize because the table does
+        let hash = (hash >> self.bitshift) as usize;
         QuadraticProbing::compute(hash, self.mask)
     }
 
     #[inline]
-    fn put_entry_resize(&mut self, el: &str, new_entry: TableEntry<T>) {
-        let mut probe = self.get_probe(el);
+    pub(crate) fn put_entry_resize(&mut self, hash: u64, new_entry: TableEntry<T, S::Key>) {
+        let mut probe = self.get_probe(hash);
         let mut hash = probe.next_probe();
         loop {
-            let entry = self.get_entry_mut(hash as usize);
+            let entry = self.get_entry_mut(hash);
             if entry.pointer.is_null() {
                 entry.pointer = new_entry.pointer;
                 entry.value = new_entry.value;
@@ -163,6 +451,7 @@ impl<T: Default + Clone + Debug> StringHashMap<T> {
     pub fn values(&self) -> impl Iterator<Item = &T> {
         self.table
             .iter()
+            .chain(self.old_table.iter().flat_map(OldTable::entries))
             .filter(|entry| !entry.pointer.is_null())
             .map(|entry| &entry.value)
     }
@@ -170,79 +459,110 @@ impl<T: Default + Clone + Debug> StringHashMap<T> {
     pub fn values_mut(&mut self) -> impl Iterator<Item = &mut T> {
         self.table
             .iter_mut()
+            .chain(self.old_table.iter_mut().flat_map(OldTable::entries_mut))
             .filter(|entry| !entry.pointer.is_null())
             .map(|entry| &mut entry.value)
     }
     #[inline]
-    pub fn keys(&self) -> KeyIterator<'_, T> {
-        KeyIterator { map: self, pos: 0 }
+    fn get_entry(&self, hash: usize) -> &TableEntry<T, S::Key> {
+        unsafe { self.table.get_unchecked(hash) }
     }
-
     #[inline]
-    pub fn iter(&self) -> impl Iterator<Item = (&str, &T)> {
-        self.table
-            .iter()
-            .filter(|entry| !entry.pointer.is_null())
-            .map(move |entry| (self.read_string(entry.pointer), &entry.value))
+    pub(crate) fn get_entry_mut(&mut self, hash: usize) -> &mut TableEntry<T, S::Key> {
+        unsafe { self.table.get_unchecked_mut(hash) }
+    }
+
+    /// Doubles the size of the table
+    /// Creates a new table and moves all entries to the new table
+    #[cold]
+    fn resize(&mut self) {
+        let table = self.new_table(self.table.len() * 2);
+        self.move_to_table(table);
+    }
+
+    /// Like `resize`, but returns an error instead of aborting when the allocator fails.
+    #[cold]
+    fn try_resize(&mut self) -> Result<(), TryReserveError> {
+        let table = self.try_new_table(self.table.len() * 2)?;
+        self.move_to_table(table);
+        Ok(())
     }
 
+    /// Returns a table with `num_buckets` empty buckets.
     #[inline]
-    pub fn iter_mut(&mut self) -> impl Iterator<Item = (&str, &mut T)> {
-        // You bested me borrow checker
-        // Cast should be fine, since self lives als long as the iter and all data accessed in read_string is immutable
-        // I don't know why but mutable access doesn't work here without errors
-        // Should be possible to fix by creating an extra Iter struct like in keys
-        let cheated_self = unsafe { &*(self as *mut StringHashMap<T> as *const StringHashMap<T>)};
-        self.table
-            .iter_mut()
-            .filter(|entry| !entry.pointer.is_null())
-            .map(move |entry| {
-                let text = cheated_self.read_string(entry.pointer);
-                (text, &mut entry.value)
-            })
+    pub(crate) fn new_table(&self, num_buckets: usize) -> Vec<TableEntry<T, S::Key>, A> {
+        let mut table = Vec::with_capacity_in(num_buckets, self.allocator().clone());
+        table.resize(num_buckets, TableEntry::default());
+        table
     }
 
+    /// Like `new_table`, but returns an error instead of aborting when the allocator fails.
     #[inline]
-    fn get_entry(&self, hash: usize) -> &TableEntry<T> {
-        unsafe { self.table.get_unchecked(hash) }
+    pub(crate) fn try_new_table(
+        &self,
+        num_buckets: usize,
+    ) -> Result<Vec<TableEntry<T, S::Key>, A>, TryReserveError> {
+        let mut table = Vec::new_in(self.allocator().clone());
+        table.try_reserve_exact(num_buckets)?;
+        table.resize(num_buckets, TableEntry::default());
+        Ok(table)
     }
+
+    /// Rehashes into the smallest power of two table which keeps the load factor.
+    ///
+    /// Tables only grow on inserts, so this shrinks a map which has been created with a larger
+    /// `with_power_of_two_size` than needed, e.g. before keeping it around for lookups. It may
+    /// also grow the table by one step, if the last insert exceeded the load factor. Use
+    /// `shrink_to_fit` to trim the unused capacity of the string data.
+    ///
+    /// # Examples
+    /// ```
+    /// use inohashmap::StringHashMap;
+    /// let mut hashmap = StringHashMap::<u32>::with_power_of_two_size(20);
+    /// hashmap.get_or_create("blub", 1);
+    /// hashmap.rehash_to_fit();
+    /// assert_eq!(hashmap.stats().num_buckets, 2);
+    /// assert_eq!(hashmap.get("blub"), Some(&1));
+    /// ```
+    pub fn rehash_to_fit(&mut self) {
+        self.finish_resize();
+        let num_buckets = (self.occupied * 3).div_ceil(2).next_power_of_two();
+        if num_buckets == self.table.len() {
+            return;
+        }
+        let table = self.new_table(num_buckets);
+        self.num_resizes += 1;
+        self.rehash_into(table);
+    }
+
+    /// Moves all entries to `table`, which has twice the size of the current table
     #[inline]
-    fn get_entry_mut(&mut self, hash: usize) -> &mut TableEntry<T> {
-        unsafe { self.table.get_unchecked_mut(hash as usize) }
+    fn move_to_table(&mut self, table: Vec<TableEntry<T, S::Key>, A>) {
+        if self.incremental_resize {
+            return self.start_incremental_resize(table);
+        }
+        self.num_resizes += 1;
+        self.rehash_into(table);
     }
 
-    /// Doubles the size of the table
-    /// Creates a new table and moves all entries to the new table
-    #[cold]
-    fn resize(&mut self) {
-        let mut table: Vec<TableEntry<T>> = vec![];
-        table.resize(self.table.len() * 2, TableEntry::default());
-        self.mask = table.len() as u32 - 1;
+    /// Moves all entries to `table` at once, `table` can have any power of two size which fits
+    /// all entries.
+    pub(crate) fn rehash_into(&mut self, mut table: Vec<TableEntry<T, S::Key>, A>) {
+        self.finish_resize();
+        self.mask = table.len() - 1;
+        self.bitshift = 63 - table.len().trailing_zeros() as usize;
 
-        std::mem::swap(&mut self.table, &mut table);
-        self.bitshift -= 1;
+        core::mem::swap(&mut self.table, &mut table);
         for entry in table.into_iter().filter(|x| !x.pointer.is_null()) {
-            let text = self.read_string(entry.pointer);
-            // casting away lifetime of text
-            // Since string_data will not be altered in put_entry_resize
-            let text = unsafe { std::mem::transmute::<&str, &'static str>(text) };
-            self.put_entry_resize(text, entry);
+            let hash = self.string_data.hash_key(&entry.pointer, &self.hasher);
+            self.put_entry_resize(hash, entry);
         }
     }
 
     #[inline]
-    fn put_in_bucket(&mut self, hash: usize, el: &str, value: T) -> &mut TableEntry<T> {
-        let pos = BytesRef(self.string_data.len() as u32);
-
-        encode_varint_into(&mut self.string_data, el.len() as u32);
-
-        self.string_data.extend_from_slice(el.as_bytes());
-        // unsafe {
-        //     self.string_data.reserve(el.len());
-        //     let target = self.string_data.as_mut_ptr().add(self.string_data.len());
-        //     std::ptr::copy_nonoverlapping(el.as_bytes().as_ptr(), target, el.as_bytes().len());
-        //     self.string_data.set_len(self.string_data.len()+ el.len() );
-        // };
+    fn put_in_bucket(&mut self, hash: usize, el: &str, value: T) -> &mut TableEntry<T, S::Key> {
+        let pos = self.string_data.insert(el);
+        self.key_bytes += el.len();
 
         let entry = self.get_entry_mut(hash);
         *entry = TableEntry {
@@ -251,28 +571,76 @@ impl<T: Default + Clone + Debug> StringHashMap<T> {
         };
         entry
     }
+}
 
+impl<
+        T: Default + Clone + Debug,
+        A: Allocator + Clone,
+        H: StringHasher,
+        S: ContiguousKeyStorage,
+    > StringHashMap<T, A, H, S>
+{
     #[inline]
-    pub(crate) fn read_string(&self, pos: BytesRef) -> &str {
-        let mut pos = pos.addr() as usize;
-        let length_string = decode_varint_slice(&self.string_data, &mut pos).unwrap();
-        unsafe {
-            std::str::from_utf8_unchecked(
-                &self
-                    .string_data
-                    .get_unchecked(pos..pos + length_string as usize),
-            )
-        }
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &T)> {
+        self.table
+            .iter()
+            .chain(self.old_table.iter().flat_map(OldTable::entries))
+            .filter(|entry| !entry.pointer.is_null())
+            .map(move |entry| (self.read_string(&entry.pointer), &entry.value))
+    }
+
+    #[inline]
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (&str, &mut T)> {
+        // You bested me borrow checker
+        // Cast should be fine, since self lives als long as the iter and all data accessed in read_string is immutable
+        // I don't know why but mutable access doesn't work here without errors
+        // Should be possible to fix by creating an extra Iter struct like in keys
+        let cheated_self = unsafe {
+            &*(self as *mut StringHashMap<T, A, H, S> as *const StringHashMap<T, A, H, S>)
+        };
+        self.table
+            .iter_mut()
+            .chain(self.old_table.iter_mut().flat_map(OldTable::entries_mut))
+            .filter(|entry| !entry.pointer.is_null())
+            .map(move |entry| {
+                let TableEntry { value, pointer } = entry;
+                (cheated_self.read_string(pointer), value)
+            })
+    }
+
+    #[inline]
+    pub(crate) fn read_string<'a>(&'a self, pos: &'a S::Key) -> &'a str {
+        self.string_data.read(pos)
+    }
+}
+
+/// Like `read_string`, but returns `None` instead of undefined behaviour if `pos` doesn't point
+/// to the start of a string.
+#[inline]
+pub(crate) fn read_string_checked(string_data: &[u8], pos: BytesRef) -> Option<&str> {
+    let mut pos = pos.addr() as usize;
+    let length_string = decode_varint_slice(string_data, &mut pos)?;
+    let bytes = string_data.get(pos..pos.checked_add(length_string as usize)?)?;
+    core::str::from_utf8(bytes).ok()
+}
+
+/// Reads the varint length prefixed string at `pos`.
+#[inline]
+pub(crate) fn read_string(string_data: &[u8], pos: BytesRef) -> &str {
+    let mut pos = pos.addr() as usize;
+    let length_string = decode_varint_slice(string_data, &mut pos).unwrap();
+    unsafe {
+        core::str::from_utf8_unchecked(string_data.get_unchecked(pos..pos + length_string as usize))
     }
 }
 
 #[derive(Debug)]
-pub struct KeyIterator<'a, T> {
-    pub map: &'a StringHashMap<T>,
+pub struct KeyIterator<'a, T, A: Allocator + Clone = Global, H: StringHasher = Yoshimitsu> {
+    pub map: &'a StringHashMap<T, A, H>,
     pos: usize,
 }
 
-impl<'a, T> Iterator for KeyIterator<'a, T> {
+impl<'a, T, A: Allocator + Clone, H: StringHasher> Iterator for KeyIterator<'a, T, A, H> {
     type Item = &'a str;
 
     #[inline]
@@ -282,9 +650,8 @@ impl<'a, T> Iterator for KeyIterator<'a, T> {
         } else {
             let length_string = decode_varint_slice(&self.map.string_data, &mut self.pos).unwrap();
             let text = unsafe {
-                std::str::from_utf8_unchecked(
-                    &self
-                        .map
+                core::str::from_utf8_unchecked(
+                    self.map
                         .string_data
                         .get_unchecked(self.pos..self.pos + length_string as usize),
                 )
@@ -296,19 +663,19 @@ impl<'a, T> Iterator for KeyIterator<'a, T> {
 }
 
 struct QuadraticProbing {
-    hash: u32,
-    i: u32,
-    mask: u32,
+    hash: usize,
+    i: usize,
+    mask: usize,
 }
 
 impl QuadraticProbing {
     #[inline]
-    fn compute(hash: u32, mask: u32) -> QuadraticProbing {
+    fn compute(hash: usize, mask: usize) -> QuadraticProbing {
         QuadraticProbing { hash, i: 1, mask }
     }
 
     #[inline]
-    fn next_probe(&mut self) -> u32 {
+    fn next_probe(&mut self) -> usize {
         self.i += 1;
         ((self.hash + (self.i + self.i * self.i)) >> 1) & self.mask
         // (self.hash + (self.i * self.i)) & self.mask
@@ -318,8 +685,11 @@ impl QuadraticProbing {
 #[cfg(test)]
 mod tests {
     use super::*;
-    #[test]
-    fn get_values_big() {
+    use std::vec::Vec;
+
+    /// Returns the text of `1342-0.txt`, whose whitespace separated words are the keys of most
+    /// tests.
+    pub(crate) fn test_words() -> String {
         use std::io::Read;
 
         let mut contents = String::new();
@@ -327,7 +697,35 @@ mod tests {
             .unwrap()
             .read_to_string(&mut contents)
             .unwrap();
+        contents
+    }
+
+    /// Counts the words of `text` in a `StringHashMap`, the reference other maps are compared
+    /// with.
+    pub(crate) fn count_words(text: &str) -> StringHashMap<u32> {
+        let mut reference = StringHashMap::<u32>::new();
+        for word in text.split_whitespace() {
+            *reference.get_or_create(word, 0) += 1;
+        }
+        reference
+    }
 
+    /// Asserts that a map with `len` keys returns the value of every key of `reference` from
+    /// `get`.
+    pub(crate) fn assert_same_values(
+        reference: &StringHashMap<u32>,
+        len: usize,
+        get: impl Fn(&str) -> Option<u32>,
+    ) {
+        assert_eq!(len, reference.len());
+        for (key, value) in reference.iter() {
+            assert_eq!(get(key), Some(*value), "{}", key);
+        }
+    }
+
+    #[test]
+    fn get_values_big() {
+        let contents = test_words();
         let mut map = StringHashMap::<u32>::new();
         let mut counter = 0;
         for text in contents.split_whitespace() {
@@ -338,17 +736,16 @@ mod tests {
 
         let sum: u32 = map.values().sum();
         assert_eq!(sum, counter);
-        assert_eq!(map.string_data.len() < 1_000_000, true);
+        assert!(map.string_data.len() < 1_000_000);
 
         dbg!(counter);
 
-        // let num_one_time_probe= map.num_probes.iter().filter(|el| *el == &1).cloned().sum::<u32>();
-        // let num_two_time_probe= map.num_probes.iter().filter(|el| *el == &2).cloned().sum::<u32>();
-        // let num_more_than_one_time_probe= map.num_probes.iter().filter(|el| *el != &1).cloned().sum::<u32>();
-        // dbg!(num_one_time_probe);
-        // dbg!(num_two_time_probe);
-        // dbg!(num_more_than_one_time_probe);
-        // dbg!(map.existing);
+        let stats = map.stats();
+        assert_eq!(stats.successful_probes.iter().sum::<usize>(), map.len());
+        // the load factor stays below 2/3
+        assert!(stats.avg_successful_probes() < 2.0);
+        assert!(stats.avg_failed_probes() < 4.0);
+        assert!(stats.avg_cluster_size() < 4.0);
     }
     #[test]
     fn values() {
@@ -441,13 +838,237 @@ mod tests {
     #[test]
     fn test_len() {
         let mut hashmap = StringHashMap::<u32>::with_power_of_two_size(1);
-        assert_eq!(hashmap.is_empty(), true);
+        assert!(hashmap.is_empty());
         hashmap.get_or_create("blub1", 3);
         assert_eq!(hashmap.len(), 1);
-        assert_eq!(hashmap.is_empty(), false);
+        assert!(!hashmap.is_empty());
         hashmap.get_or_create("blub2", 4);
         hashmap.get_or_create("blub3", 5);
         assert_eq!(hashmap.len(), 3);
-        assert_eq!(hashmap.is_empty(), false);
+        assert!(!hashmap.is_empty());
+    }
+    #[test]
+    fn test_rehash_to_fit() {
+        let mut hashmap = StringHashMap::<u32>::with_power_of_two_size(16);
+        for i in 0..100 {
+            hashmap.get_or_create(&format!("blub{}", i), i);
+        }
+        hashmap.rehash_to_fit();
+        let stats = hashmap.stats();
+        assert_eq!(stats.num_buckets, 256);
+        assert_eq!(stats.num_resizes, 1);
+        assert!(stats.load_factor <= 1.0 / 1.5);
+        for i in 0..100 {
+            assert_eq!(hashmap.get(&format!("blub{}", i)), Some(&i));
+        }
+        // the map still grows after shrinking
+        for i in 100..1000 {
+            hashmap.get_or_create(&format!("blub{}", i), i);
+        }
+        assert_eq!(hashmap.len(), 1000);
+        assert_eq!(hashmap.get("blub999"), Some(&999));
+
+        // an incremental resize is finished first
+        hashmap.set_incremental_resize(true);
+        while !hashmap.is_resizing() {
+            hashmap.get_or_create(&format!("blub{}", hashmap.len()), 0);
+        }
+        hashmap.rehash_to_fit();
+        assert!(!hashmap.is_resizing());
+        assert_eq!(hashmap.values().count(), hashmap.len());
+
+        let mut hashmap = StringHashMap::<u32>::new();
+        hashmap.rehash_to_fit();
+        assert_eq!(hashmap.stats().num_buckets, 1);
+        hashmap.get_or_create("blub", 1);
+        assert_eq!(hashmap.get("blub"), Some(&1));
+    }
+
+    #[test]
+    fn test_mem_usage() {
+        let mut hashmap = StringHashMap::<u32>::with_power_of_two_size(4);
+        hashmap.get_or_create("blub1", 3);
+        hashmap.get_or_create("blub2", 4);
+
+        let usage = hashmap.mem_usage();
+        assert_eq!(usage.table, 8 * std::mem::size_of::<TableEntry<u32>>());
+        assert_eq!(usage.string_data, hashmap.string_data.capacity());
+        assert_eq!(
+            usage.string_data_unused,
+            hashmap.string_data.capacity() - 12
+        );
+        assert_eq!(usage.varint_overhead, 2);
+        assert_eq!(usage.values_heap, 0);
+        assert_eq!(usage.total(), usage.table + usage.string_data);
+
+        let mut hashmap = StringHashMap::<Vec<u32>>::with_power_of_two_size(4);
+        hashmap.get_or_create("blub1", Vec::with_capacity(4));
+        hashmap.get_or_create("blub2", Vec::with_capacity(2));
+        assert_eq!(hashmap.mem_usage().values_heap, 0);
+        assert_eq!(hashmap.mem_usage_with_heap().values_heap, 24);
+    }
+
+    #[test]
+    fn test_hashed_key() {
+        let mut title = StringHashMap::<u32>::with_power_of_two_size(1);
+        let mut body = StringHashMap::<u32>::with_power_of_two_size(1);
+        for i in 0..100 {
+            let key = format!("blub{}", i % 20);
+            let key = HashedKey::new(&key);
+            *title.get_or_create(key, 0) += 1;
+            *body.get_or_create(key, 0) += 2;
+        }
+        let key = HashedKey::from_hash("blub3", hasher::fnv32a_yoshimitsu_hasher(b"blub3"));
+        assert_eq!(key, HashedKey::new("blub3"));
+        assert_eq!(title.get(key), Some(&5));
+        assert_eq!(body.get_mut(key), Some(&mut 10));
+        assert_eq!(body.get(HashedKey::new("blub20")), None);
+        assert_eq!(title.get_or_create(HashedKey::new("blub20"), 1), &1);
+        assert_eq!(title.len(), 21);
+    }
+
+    fn same_as_default_hasher<H: StringHasher>(hasher: H) {
+        let contents = test_words();
+        let mut map = StringHashMap::<u32, Global, H>::with_power_of_two_size_and_hasher(1, hasher);
+        for text in contents.split_whitespace() {
+            *map.get_or_create(text, 0) += 1;
+        }
+        let reference = count_words(&contents);
+        assert_same_values(&reference, map.len(), |key| map.get(key).copied());
+        assert_same_values(&reference, map.len(), |key| {
+            map.get(map.hash_key(key)).copied()
+        });
+        *map.entry("blub").or_default() += 1;
+        assert_eq!(map.get("blub"), Some(&1));
+    }
+
+    #[test]
+    fn test_hashers() {
+        same_as_default_hasher(hasher::Murmur2);
+        same_as_default_hasher(hasher::XxHash32);
+        same_as_default_hasher(hasher::WyHash);
+    }
+
+    #[test]
+    fn test_hash64() {
+        let mut hashmap =
+            StringHashMap::<u32, Global, _>::with_power_of_two_size_and_hasher(4, hasher::WyHash);
+        for i in 0..1000 {
+            hashmap.get_or_create(&i.to_string(), i);
+        }
+        let key = hashmap.hash_key("500");
+        assert_eq!(key.hash64(), hasher::wyhash(0, b"500"));
+        assert_eq!(
+            hashmap.get(HashedKey::from_hash64("500", key.hash64())),
+            Some(&500)
+        );
+        // the table is small, the low half of the hash is not used yet
+        assert_eq!(
+            hashmap.get(HashedKey::from_hash("500", key.hash())),
+            Some(&500)
+        );
+    }
+
+    #[test]
+    #[cfg(target_pointer_width = "64")]
+    fn probe_beyond_u32() {
+        // a table with 2^34 buckets
+        let hash = 0x8000_0000_0000_0000u64 >> (64 - 35);
+        let mut probe = QuadraticProbing::compute(hash as usize, (1 << 34) - 1);
+        let bucket = probe.next_probe();
+        assert!(bucket > u32::MAX as usize);
+        assert_eq!(bucket, (((hash as usize) + 6) >> 1) & ((1 << 34) - 1));
+    }
+
+    #[test]
+    #[cfg(target_pointer_width = "64")]
+    fn large_power_of_two() {
+        let contents = test_words();
+        let mut hashmap = StringHashMap::<u32>::with_power_of_two_size(24);
+        assert_eq!(hashmap.table.len(), 1 << 23);
+        for (i, text) in contents.split_whitespace().enumerate() {
+            hashmap.get_or_create(text, i as u32);
+        }
+        assert_eq!(hashmap.num_resizes, 0);
+        let mut reference = std::collections::HashMap::new();
+        for (i, text) in contents.split_whitespace().enumerate() {
+            reference.entry(text).or_insert(i as u32);
+        }
+        assert_eq!(hashmap.len(), reference.len());
+        for (key, value) in reference {
+            assert_eq!(hashmap.get(key), Some(&value));
+        }
+    }
+
+    /// Allocator which fails once more than `limit` bytes are allocated
+    #[derive(Clone)]
+    pub(crate) struct LimitedAlloc {
+        pub(crate) allocated: std::rc::Rc<std::cell::Cell<usize>>,
+        pub(crate) limit: usize,
+    }
+
+    unsafe impl Allocator for LimitedAlloc {
+        fn allocate(
+            &self,
+            layout: std::alloc::Layout,
+        ) -> Result<std::ptr::NonNull<[u8]>, AllocError> {
+            if self.allocated.get() + layout.size() > self.limit {
+                return Err(AllocError);
+            }
+            self.allocated.set(self.allocated.get() + layout.size());
+            Global.allocate(layout)
+        }
+
+        unsafe fn deallocate(&self, ptr: std::ptr::NonNull<u8>, layout: std::alloc::Layout) {
+            self.allocated.set(self.allocated.get() - layout.size());
+            Global.deallocate(ptr, layout)
+        }
+    }
+
+    #[test]
+    fn test_allocator() {
+        let alloc = LimitedAlloc {
+            allocated: Default::default(),
+            limit: usize::MAX,
+        };
+        let mut hashmap = StringHashMap::<u32, _>::with_power_of_two_size_in(1, alloc.clone());
+        hashmap.get_or_create("blub1", 3);
+        hashmap.get_or_create("blub2", 4);
+        hashmap.get_or_create("blub3", 5);
+        assert_eq!(hashmap.get("blub1"), Some(&3));
+        assert_eq!(
+            alloc.allocated.get(),
+            hashmap.table.capacity() * std::mem::size_of::<TableEntry<u32>>()
+                + hashmap.string_data.capacity()
+        );
+        drop(hashmap);
+        assert_eq!(alloc.allocated.get(), 0);
+    }
+
+    #[test]
+    fn test_allocator_error() {
+        let alloc = LimitedAlloc {
+            allocated: Default::default(),
+            limit: 1024,
+        };
+        assert!(StringHashMap::<u32, _>::try_with_power_of_two_size_in(10, alloc.clone()).is_err());
+        #[cfg(target_pointer_width = "64")]
+        assert!(StringHashMap::<u32, _>::try_with_power_of_two_size_in(48, alloc.clone()).is_err());
+
+        let mut hashmap =
+            StringHashMap::<u32, _>::try_with_power_of_two_size_in(2, alloc.clone()).unwrap();
+        let mut num_inserted = 0;
+        while hashmap
+            .try_get_or_create(&format!("blub{}", num_inserted), num_inserted)
+            .is_ok()
+        {
+            num_inserted += 1;
+        }
+        assert!(num_inserted > 0);
+        assert_eq!(hashmap.len(), num_inserted as usize);
+        for i in 0..num_inserted {
+            assert_eq!(hashmap.get(&format!("blub{}", i)), Some(&i));
+        }
+        assert!(alloc.allocated.get() <= 1024);
     }
 }
@@ -0,0 +1,96 @@
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// Memory used by a `StringHashMap`, in bytes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MemUsage {
+    /// Allocated size of the table, including empty buckets.
+    pub table: usize,
+    /// Allocated size of the string data, including unused capacity.
+    pub string_data: usize,
+    /// Unused capacity of the string data. Already contained in `string_data`.
+    pub string_data_unused: usize,
+    /// Bytes spent on the varint length prefix of each key. Already contained in `string_data`.
+    pub varint_overhead: usize,
+    /// Heap memory owned by the values, as reported by `HeapSize`.
+    pub values_heap: usize,
+}
+
+impl MemUsage {
+    /// Total number of bytes owned by the map.
+    #[inline]
+    pub fn total(&self) -> usize {
+        self.table + self.string_data + self.values_heap
+    }
+}
+
+/// Reports heap memory owned by a value, excluding the value itself.
+///
+/// Implement this for value types which own allocations to have them accounted in
+/// `StringHashMap::mem_usage_with_heap`.
+pub trait HeapSize {
+    fn heap_size(&self) -> usize;
+}
+
+macro_rules! impl_heap_size_zero {
+    ($($ty:ty),*) => {
+        $(
+            impl HeapSize for $ty {
+                #[inline]
+                fn heap_size(&self) -> usize {
+                    0
+                }
+            }
+        )*
+    };
+}
+
+impl_heap_size_zero!(
+    (),
+    bool,
+    char,
+    u8,
+    u16,
+    u32,
+    u64,
+    u128,
+    usize,
+    i8,
+    i16,
+    i32,
+    i64,
+    i128,
+    isize,
+    f32,
+    f64
+);
+
+impl HeapSize for String {
+    #[inline]
+    fn heap_size(&self) -> usize {
+        self.capacity()
+    }
+}
+
+impl<T: HeapSize> HeapSize for Vec<T> {
+    #[inline]
+    fn heap_size(&self) -> usize {
+        self.capacity() * core::mem::size_of::<T>()
+            + self.iter().map(HeapSize::heap_size).sum::<usize>()
+    }
+}
+
+impl<T: HeapSize> HeapSize for Box<T> {
+    #[inline]
+    fn heap_size(&self) -> usize {
+        core::mem::size_of::<T>() + (**self).heap_size()
+    }
+}
+
+impl<T: HeapSize> HeapSize for Option<T> {
+    #[inline]
+    fn heap_size(&self) -> usize {
+        self.as_ref().map(HeapSize::heap_size).unwrap_or(0)
+    }
+}
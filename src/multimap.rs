@@ -0,0 +1,254 @@
+/*! Hashmap with a growing list of values per key, e.g. the postings of an inverted index.
+
+`StringMultiMap` stores the values of all keys in a single arena, as linked lists of blocks. The
+block size doubles with every block of a list up to `MAX_BLOCK_LEN`, like the unrolled linked
+list of tantivy. Short lists waste little space and long lists need few jumps between blocks.
+There is no heap allocation per key.
+
+# Examples
+```
+use inohashmap::StringMultiMap;
+let mut postings = StringMultiMap::<u32>::new();
+for (doc, text) in ["a b", "b c", "b"].iter().enumerate() {
+    for term in text.split_whitespace() {
+        postings.push(term, doc as u32);
+    }
+}
+assert_eq!(postings.iter_values("b").collect::<Vec<_>>(), vec![0, 1, 2]);
+assert_eq!(postings.iter_values("d").count(), 0);
+```
+
+*/
+
+use crate::StringHashMap;
+use allocator_api2::vec::Vec;
+use core::convert::TryFrom;
+
+/// Length of the first block of a list.
+const FIRST_BLOCK_LEN: u32 = 2;
+/// Blocks stop growing at this length.
+const MAX_BLOCK_LEN: u32 = 1 << 15;
+
+/// Marks the end of a list of blocks.
+const NO_BLOCK: u32 = u32::MAX;
+
+#[derive(Debug)]
+pub struct StringMultiMap<V: Copy> {
+    lists: StringHashMap<ListState>,
+    blocks: Vec<Block>,
+    /// values of all lists
+    arena: Vec<V>,
+}
+
+/// A range of `len` values in the arena, followed by the values in the `next` block.
+#[derive(Debug, Clone, Copy)]
+struct Block {
+    start: u32,
+    len: u32,
+    next: u32,
+}
+
+/// Stored in the map for every key.
+#[derive(Debug, Clone, Copy)]
+struct ListState {
+    first_block: u32,
+    last_block: u32,
+    /// number of values in the list
+    len: u32,
+    /// number of values in the last block
+    last_block_len: u32,
+}
+
+impl Default for ListState {
+    fn default() -> Self {
+        ListState {
+            first_block: NO_BLOCK,
+            last_block: NO_BLOCK,
+            len: 0,
+            last_block_len: 0,
+        }
+    }
+}
+
+impl<V: Copy> Default for StringMultiMap<V> {
+    fn default() -> Self {
+        StringMultiMap::with_power_of_two_size(10)
+    }
+}
+
+impl<V: Copy> StringMultiMap<V> {
+    /// `power_of_two` sizes the table of keys like `StringHashMap::with_power_of_two_size`.
+    #[inline]
+    pub fn with_power_of_two_size(power_of_two: usize) -> Self {
+        StringMultiMap {
+            lists: StringHashMap::with_power_of_two_size(power_of_two),
+            blocks: Vec::new(),
+            arena: Vec::new(),
+        }
+    }
+    #[inline]
+    pub fn new() -> Self {
+        Self::with_power_of_two_size(10)
+    }
+
+    /// Returns the number of keys.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.lists.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.lists.is_empty()
+    }
+
+    /// Returns the number of values of all keys.
+    #[inline]
+    pub fn num_values(&self) -> usize {
+        self.lists.values().map(|list| list.len as usize).sum()
+    }
+
+    #[inline]
+    pub fn shrink_to_fit(&mut self) {
+        self.lists.shrink_to_fit();
+        self.blocks.shrink_to_fit();
+        self.arena.shrink_to_fit();
+    }
+
+    /// Appends `value` to the values of `el`.
+    #[inline]
+    pub fn push(&mut self, el: &str, value: V) {
+        let list = self.lists.get_or_create(el, ListState::default());
+        if list.first_block == NO_BLOCK
+            || list.last_block_len == self.blocks[list.last_block as usize].len
+        {
+            // the new block has the size of all previous ones together
+            let len = list.len.clamp(FIRST_BLOCK_LEN, MAX_BLOCK_LEN);
+            let block =
+                u32::try_from(self.blocks.len()).expect("StringMultiMap exceeds u32::MAX blocks");
+            // the end of the block has to fit into a u32 too
+            let start = u32::try_from(self.arena.len())
+                .ok()
+                .filter(|start| start.checked_add(len).is_some())
+                .expect("StringMultiMap exceeds u32::MAX values");
+            self.blocks.push(Block {
+                start,
+                len,
+                next: NO_BLOCK,
+            });
+            // the block is filled with copies of `value`, which are overwritten by the next pushes
+            self.arena.resize(self.arena.len() + len as usize, value);
+            if list.first_block == NO_BLOCK {
+                list.first_block = block;
+            } else {
+                self.blocks[list.last_block as usize].next = block;
+            }
+            list.last_block = block;
+            list.last_block_len = 0;
+        }
+        let pos = self.blocks[list.last_block as usize].start + list.last_block_len;
+        self.arena[pos as usize] = value;
+        list.last_block_len += 1;
+        list.len += 1;
+    }
+
+    /// Returns the values of `el` in the order they were pushed, nothing if `el` is not in the
+    /// map.
+    #[inline]
+    pub fn iter_values(&self, el: &str) -> impl Iterator<Item = V> + '_ {
+        let list = self.lists.get(el).copied().unwrap_or_default();
+        self.list_values(list)
+    }
+
+    /// Returns the number of values of `el`.
+    #[inline]
+    pub fn num_values_of(&self, el: &str) -> usize {
+        self.lists.get(el).map_or(0, |list| list.len as usize)
+    }
+
+    /// Returns the keys in insertion order.
+    #[inline]
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.lists.keys()
+    }
+
+    /// Returns the keys with their values, in table order.
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = (&str, impl Iterator<Item = V> + '_)> {
+        self.lists
+            .iter()
+            .map(move |(key, list)| (key, self.list_values(*list)))
+    }
+
+    #[inline]
+    fn list_values(&self, list: ListState) -> impl Iterator<Item = V> + '_ {
+        let mut block = list.first_block;
+        let mut remaining = list.len;
+        core::iter::from_fn(move || {
+            if remaining == 0 {
+                return None;
+            }
+            let Block { start, len, next } = self.blocks[block as usize];
+            let len = len.min(remaining);
+            remaining -= len;
+            block = next;
+            Some(&self.arena[start as usize..(start + len) as usize])
+        })
+        .flat_map(|values| values.iter().copied())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::vec::Vec;
+
+    #[test]
+    fn push_and_iter() {
+        let mut map = StringMultiMap::<u64>::with_power_of_two_size(4);
+        let mut expected: std::collections::HashMap<String, Vec<u64>> = Default::default();
+        // interleave the keys, so their blocks are mixed in the arena
+        for i in 0..100_000u64 {
+            let key = format!("key{}", (i * 7919) % 97 % (1 + i % 50));
+            map.push(&key, i);
+            expected.entry(key).or_default().push(i);
+        }
+        assert_eq!(map.len(), expected.len());
+        assert_eq!(map.num_values(), 100_000);
+        for (key, values) in expected.iter() {
+            assert_eq!(&map.iter_values(key).collect::<Vec<_>>(), values);
+            assert_eq!(map.num_values_of(key), values.len());
+        }
+        for (key, values) in map.iter() {
+            assert!(values.eq(expected[key].iter().copied()));
+        }
+        assert_eq!(map.iter_values("missing").count(), 0);
+        assert_eq!(map.num_values_of("missing"), 0);
+        assert_eq!(map.keys().count(), expected.len());
+    }
+
+    #[test]
+    fn block_sizes() {
+        let mut map = StringMultiMap::<u8>::new();
+        for _ in 0..100_000 {
+            map.push("a", 1);
+        }
+        map.push("b", 2);
+        let lens = map.blocks.iter().map(|block| block.len).collect::<Vec<_>>();
+        assert_eq!(
+            &lens[..4],
+            &[
+                FIRST_BLOCK_LEN,
+                FIRST_BLOCK_LEN,
+                2 * FIRST_BLOCK_LEN,
+                4 * FIRST_BLOCK_LEN
+            ]
+        );
+        assert!(lens.iter().all(|len| *len <= MAX_BLOCK_LEN));
+        assert_eq!(lens.last(), Some(&FIRST_BLOCK_LEN));
+        // less than a block of unused slots per key
+        assert!(map.arena.len() < 100_001 + MAX_BLOCK_LEN as usize + FIRST_BLOCK_LEN as usize);
+        assert_eq!(map.iter_values("a").count(), 100_000);
+        assert_eq!(map.iter_values("b").collect::<Vec<_>>(), vec![2]);
+    }
+}
@@ -0,0 +1,285 @@
+/*! Hashmap with a namespace per key, whose namespaces share one copy of every string.
+
+`NamespacedStringHashMap` is meant for separate dictionaries like one per field of a document,
+which often contain the same strings. Each namespace is a `StringHashMap`, and all of them store
+their keys in one `StringPool`.
+
+# Examples
+```
+use inohashmap::NamespacedStringHashMap;
+const TITLE: u16 = 0;
+const BODY: u16 = 1;
+let mut terms = NamespacedStringHashMap::<u32>::new();
+*terms.get_or_create(TITLE, "blub", 0) += 1;
+*terms.get_or_create(BODY, "blub", 0) += 1;
+*terms.get_or_create(BODY, "blub", 0) += 1;
+assert_eq!(terms.get(TITLE, "blub"), Some(&1));
+assert_eq!(terms.get(BODY, "blub"), Some(&2));
+// both namespaces use the same copy of "blub"
+assert_eq!(terms.num_distinct_keys(), 1);
+assert_eq!(terms.iter_namespace(BODY).collect::<Vec<_>>(), vec![("blub", &2)]);
+```
+
+*/
+
+use crate::hasher::Yoshimitsu;
+use crate::pool::OwnedPool;
+use crate::{Global, Stats, StringHashMap, StringPool};
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::fmt::Debug;
+use core::ptr::NonNull;
+
+type NamespaceMap<T> = StringHashMap<T, Global, Yoshimitsu, OwnedPool<Global>>;
+
+#[derive(Debug)]
+pub struct NamespacedStringHashMap<T> {
+    /// map of every namespace with keys, sorted by namespace
+    namespaces: Vec<(u16, NamespaceMap<T>)>,
+    /// initial table size of a namespace
+    power_of_two: usize,
+    incremental_resize: bool,
+    /// contains the keys of all namespaces, a leaked `Box` which is dropped after the maps which
+    /// point to it
+    pool: NonNull<StringPool>,
+}
+
+// The pool is not handed out, so it is only reachable through the map, and it is only changed by
+// methods which take `&mut self`.
+unsafe impl<T: Send> Send for NamespacedStringHashMap<T> {}
+unsafe impl<T: Sync> Sync for NamespacedStringHashMap<T> {}
+
+impl<T> Drop for NamespacedStringHashMap<T> {
+    fn drop(&mut self) {
+        self.namespaces.clear();
+        drop(unsafe { Box::from_raw(self.pool.as_ptr()) });
+    }
+}
+
+impl<T: Default + Clone + Debug> Default for NamespacedStringHashMap<T> {
+    fn default() -> Self {
+        NamespacedStringHashMap::with_power_of_two_size(10)
+    }
+}
+
+impl<T: Default + Clone + Debug> NamespacedStringHashMap<T> {
+    /// Creates a map whose namespaces start with a table of `power_of_two` size.
+    #[inline]
+    pub fn with_power_of_two_size(power_of_two: usize) -> Self {
+        NamespacedStringHashMap {
+            namespaces: Vec::new(),
+            power_of_two,
+            incremental_resize: false,
+            pool: NonNull::from(Box::leak(Box::new(StringPool::new()))),
+        }
+    }
+    #[inline]
+    pub fn new() -> Self {
+        Self::with_power_of_two_size(10)
+    }
+
+    /// Returns the number of keys of all namespaces.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.namespaces.iter().map(|(_, map)| map.len()).sum()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the number of distinct keys of all namespaces, each of them is stored once.
+    #[inline]
+    pub fn num_distinct_keys(&self) -> usize {
+        self.pool().len()
+    }
+
+    /// Enables or disables incremental resizing of all namespaces, see
+    /// `StringHashMap::set_incremental_resize`.
+    #[inline]
+    pub fn set_incremental_resize(&mut self, incremental: bool) {
+        self.incremental_resize = incremental;
+        for (_, map) in &mut self.namespaces {
+            map.set_incremental_resize(incremental);
+        }
+    }
+
+    /// Computes diagnostic statistics about the table of `namespace`, see
+    /// `StringHashMap::stats`.
+    #[inline]
+    pub fn stats(&self, namespace: u16) -> Option<Stats> {
+        self.namespace(namespace).map(|map| map.stats())
+    }
+
+    #[inline]
+    pub fn shrink_to_fit(&mut self) {
+        self.namespaces.shrink_to_fit();
+        for (_, map) in &mut self.namespaces {
+            map.shrink_to_fit();
+        }
+    }
+
+    #[inline]
+    pub fn get(&self, namespace: u16, el: &str) -> Option<&T> {
+        self.namespace(namespace)?.get(el)
+    }
+
+    #[inline]
+    pub fn get_mut(&mut self, namespace: u16, el: &str) -> Option<&mut T> {
+        let pos = self.namespace_pos(namespace).ok()?;
+        self.namespaces[pos].1.get_mut(el)
+    }
+
+    #[inline]
+    pub fn get_or_create(&mut self, namespace: u16, el: &str, value: T) -> &mut T {
+        let pos = match self.namespace_pos(namespace) {
+            Ok(pos) => pos,
+            Err(pos) => {
+                // the pool is dropped after the maps
+                let pool = unsafe { OwnedPool::new(self.pool) };
+                let mut map =
+                    StringHashMap::with_power_of_two_size_and_pool(self.power_of_two, pool);
+                map.set_incremental_resize(self.incremental_resize);
+                self.namespaces.insert(pos, (namespace, map));
+                pos
+            }
+        };
+        self.namespaces[pos].1.get_or_create(el, value)
+    }
+
+    /// Returns the keys and values of `namespace`, in table order.
+    #[inline]
+    pub fn iter_namespace(&self, namespace: u16) -> impl Iterator<Item = (&str, &T)> {
+        self.namespace(namespace)
+            .into_iter()
+            .flat_map(|map| map.iter())
+    }
+
+    #[inline]
+    pub fn iter_namespace_mut(&mut self, namespace: u16) -> impl Iterator<Item = (&str, &mut T)> {
+        let map = match self.namespace_pos(namespace) {
+            Ok(pos) => Some(&mut self.namespaces[pos].1),
+            Err(_) => None,
+        };
+        map.into_iter().flat_map(|map| map.iter_mut())
+    }
+
+    /// Returns the namespaces, keys and values of all namespaces, by namespace and in table order
+    /// within a namespace.
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = (u16, &str, &T)> {
+        self.namespaces.iter().flat_map(|(namespace, map)| {
+            map.iter().map(move |(key, value)| (*namespace, key, value))
+        })
+    }
+
+    #[inline]
+    fn pool(&self) -> &StringPool {
+        unsafe { self.pool.as_ref() }
+    }
+
+    #[inline]
+    fn namespace(&self, namespace: u16) -> Option<&NamespaceMap<T>> {
+        let pos = self.namespace_pos(namespace).ok()?;
+        Some(&self.namespaces[pos].1)
+    }
+
+    /// Returns `Ok` with the position of `namespace` in `namespaces`, or `Err` with the position
+    /// where it would be inserted.
+    #[inline]
+    fn namespace_pos(&self, namespace: u16) -> Result<usize, usize> {
+        self.namespaces
+            .binary_search_by_key(&namespace, |(entry_namespace, _)| *entry_namespace)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{assert_same_values, test_words};
+    use crate::StringHashMap;
+    use std::vec::Vec;
+
+    #[test]
+    fn same_as_separate_maps() {
+        let contents = test_words();
+        let mut map = NamespacedStringHashMap::<u32>::with_power_of_two_size(1);
+        let mut separate = (0..3)
+            .map(|_| StringHashMap::<u32>::new())
+            .collect::<Vec<_>>();
+        for (line_number, line) in contents.lines().enumerate() {
+            let namespace = (line_number % 3) as u16;
+            for text in line.split_whitespace() {
+                *map.get_or_create(namespace, text, 0) += 1;
+                *separate[namespace as usize].get_or_create(text, 0) += 1;
+            }
+        }
+        assert_eq!(
+            map.len(),
+            separate.iter().map(|map| map.len()).sum::<usize>()
+        );
+        // the keys are stored once
+        let distinct_keys = separate
+            .iter()
+            .flat_map(|map| map.keys())
+            .collect::<std::collections::HashSet<_>>();
+        assert_eq!(map.num_distinct_keys(), distinct_keys.len());
+        assert!(map.num_distinct_keys() < map.len());
+        for (namespace, reference) in separate.iter().enumerate() {
+            let namespace = namespace as u16;
+            assert_same_values(reference, map.iter_namespace(namespace).count(), |key| {
+                map.get(namespace, key).copied()
+            });
+            for (key, value) in map.iter_namespace(namespace) {
+                assert_eq!(reference.get(key), Some(value));
+            }
+        }
+        assert_eq!(map.get(3, "the"), None);
+        assert_eq!(map.get(0, "not in the map"), None);
+    }
+
+    #[test]
+    fn namespaces_are_separate() {
+        let mut map = NamespacedStringHashMap::<u32>::new();
+        map.get_or_create(0, "blub", 1);
+        map.get_or_create(u16::MAX, "blub", 2);
+        map.get_or_create(0, "bla", 3);
+        *map.get_mut(u16::MAX, "blub").unwrap() += 1;
+        for (_, value) in map.iter_namespace_mut(0) {
+            *value *= 10;
+        }
+        assert_eq!(map.get(0, "blub"), Some(&10));
+        assert_eq!(map.get(u16::MAX, "blub"), Some(&3));
+        assert_eq!(map.get(1, "blub"), None);
+        assert_eq!(map.get_mut(u16::MAX, "bla"), None);
+        let mut entries = map.iter().collect::<Vec<_>>();
+        entries.sort();
+        assert_eq!(
+            entries,
+            vec![(0, "bla", &30), (0, "blub", &10), (u16::MAX, "blub", &3)]
+        );
+        assert_eq!(map.num_distinct_keys(), 2);
+    }
+
+    #[test]
+    fn incremental_resize_and_stats() {
+        let mut map = NamespacedStringHashMap::<u32>::with_power_of_two_size(1);
+        map.set_incremental_resize(true);
+        for i in 0..1000 {
+            map.get_or_create(i % 2, &(i / 2).to_string(), i as u32);
+        }
+        assert_eq!(map.len(), 1000);
+        assert_eq!(map.num_distinct_keys(), 500);
+        let stats = map.stats(1).unwrap();
+        assert_eq!(stats.len, 500);
+        assert!(stats.num_resizes > 0);
+        assert_eq!(map.stats(2), None);
+        assert_eq!(map.get(1, "499"), Some(&999));
+
+        fn assert_send_sync<T: Send + Sync>(_: &T) {}
+        assert_send_sync(&map);
+        let map = std::thread::spawn(move || map).join().unwrap();
+        assert_eq!(map.get(0, "0"), Some(&0));
+    }
+}
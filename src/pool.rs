@@ -0,0 +1,718 @@
+/*! Append-only string pool shared by several maps.
+
+A `StringHashMap` stores its keys in a `KeyStorage`. By default this is its own `Vec<u8, A>`, but
+it can also be a `StringPool` shared with other maps, so several maps with different value types
+store each key once. A key inserted into one of them reuses the bytes already stored for another.
+
+The maps hold the pool as `&StringPool`, `Rc<StringPool>` or `Arc<StringPool>`, so a map can't be
+used with a different pool than its own. The pool never moves the bytes of a key, so keys returned
+by one map stay valid while other maps append to the pool. It can't be shared between threads.
+
+# Examples
+```
+use inohashmap::{StringHashMap, StringPool};
+
+#[derive(Debug, Default, Clone, PartialEq)]
+struct Stats {
+    first_seen: u32,
+}
+
+let pool = StringPool::new();
+let mut counts = StringHashMap::<u32, _, _, _>::with_pool(&pool);
+let mut stats = StringHashMap::<Stats, _, _, _>::with_pool(&pool);
+for (line, text) in ["blub bla", "blub"].iter().enumerate() {
+    for term in text.split_whitespace() {
+        *counts.get_or_create(term, 0) += 1;
+        stats.get_or_create(term, Stats { first_seen: line as u32 });
+    }
+}
+assert_eq!(counts.get("blub"), Some(&2));
+assert_eq!(stats.get("bla"), Some(&Stats { first_seen: 0 }));
+// both maps use the same copy of "blub" and "bla"
+assert_eq!(pool.len(), 2);
+```
+
+*/
+
+use crate::bytesref::BytesRef;
+use crate::hasher::{StringHasher, Yoshimitsu};
+use crate::vint::{encode_varint, encode_varint_into};
+use crate::{read_string, read_string_checked, HashedKey, MemUsage, StringHashMap, TableEntry};
+use alloc::rc::Rc;
+#[cfg(target_has_atomic = "ptr")]
+use alloc::sync::Arc;
+use allocator_api2::alloc::{Allocator, Global};
+use allocator_api2::collections::TryReserveError;
+use allocator_api2::vec::Vec;
+use core::cell::UnsafeCell;
+use core::convert::TryFrom;
+use core::fmt::{self, Debug};
+use core::ops::Deref;
+use core::ptr::NonNull;
+use private::{ContiguousStorage, Storage};
+
+/// Storage of the keys of a `StringHashMap`.
+///
+/// Implemented for `Vec<u8, A>`, which is owned by the map, for `&StringPool`,
+/// `Rc<StringPool>` and `Arc<StringPool>`, which are shared with other maps, for
+/// `PrefixKeyStorage`, which front codes the keys, and for `InlineKeyStorage`, which stores short
+/// keys in the table entries.
+pub trait KeyStorage: private::Storage {}
+
+impl<S: private::Storage> KeyStorage for S {}
+
+/// Storage which keeps every key as one contiguous string, so keys can be borrowed as `&str`,
+/// e.g. by `iter`.
+///
+/// Implemented by all storages except `PrefixKeyStorage`.
+pub trait ContiguousKeyStorage: KeyStorage + private::ContiguousStorage {}
+
+impl<S: private::ContiguousStorage> ContiguousKeyStorage for S {}
+
+pub(crate) mod private {
+    use crate::bytesref::BytesRef;
+    use crate::hasher::StringHasher;
+    use crate::MemUsage;
+    use allocator_api2::collections::TryReserveError;
+    use core::fmt::Debug;
+
+    pub trait Storage {
+        /// Refers to a key from a table entry.
+        type Key: KeySlot;
+        /// Appends `key`, which is not in the map yet, or returns it from a shared pool.
+        fn insert(&mut self, key: &str) -> Self::Key;
+        /// Returns true if the key at `pointer`, which has been returned by `insert`, is `key`.
+        fn key_equals(&self, pointer: &Self::Key, key: &str) -> bool;
+        /// Hashes the key at `pointer` with `hasher`, e.g. to move it to a new table.
+        fn hash_key<H: StringHasher>(&self, pointer: &Self::Key, hasher: &H) -> u64;
+        /// Returns the address of the key at `pointer`, to prefetch it.
+        fn key_ptr(&self, pointer: &Self::Key) -> *const u8;
+        fn try_reserve(&mut self, additional: usize) -> Result<(), TryReserveError>;
+        fn shrink_to_fit(&mut self);
+        /// Returns the string data fields of `MemUsage`. `key_bytes` is the sum of the lengths
+        /// of the keys in the storage.
+        fn mem_usage(&self, key_bytes: usize) -> MemUsage;
+    }
+
+    pub trait ContiguousStorage: Storage {
+        /// Reads the key at `pointer`, which has been returned by `insert`.
+        fn read<'a>(&'a self, pointer: &'a Self::Key) -> &'a str;
+        /// Like `read`, but returns `None` if `pointer` doesn't point to the start of a key.
+        fn read_checked<'a>(&'a self, pointer: &'a Self::Key) -> Option<&'a str>;
+    }
+
+    /// Key of a table entry. The default value marks an empty bucket.
+    pub trait KeySlot: Copy + Default + Debug {
+        fn is_null(&self) -> bool;
+        /// Marks a bucket of the old table of an incremental resize, whose entry has been moved
+        /// to the new table.
+        fn moved() -> Self;
+        fn is_moved(&self) -> bool;
+    }
+
+    /// Marks a moved bucket, no key starts at this position.
+    const MOVED: u32 = u32::MAX - 1;
+
+    impl KeySlot for BytesRef {
+        #[inline]
+        fn is_null(&self) -> bool {
+            BytesRef::is_null(self)
+        }
+
+        #[inline]
+        fn moved() -> Self {
+            BytesRef(MOVED)
+        }
+
+        #[inline]
+        fn is_moved(&self) -> bool {
+            self.addr() == MOVED
+        }
+    }
+}
+
+impl<A: Allocator + Clone> private::Storage for Vec<u8, A> {
+    type Key = BytesRef;
+
+    #[inline]
+    fn insert(&mut self, key: &str) -> BytesRef {
+        let pos = BytesRef(u32::try_from(self.len()).expect("string data exceeds 4GB"));
+
+        encode_varint_into(
+            self,
+            u32::try_from(key.len()).expect("key is longer than 4GB"),
+        );
+
+        self.extend_from_slice(key.as_bytes());
+        // unsafe {
+        //     self.reserve(key.len());
+        //     let target = self.as_mut_ptr().add(self.len());
+        //     std::ptr::copy_nonoverlapping(key.as_bytes().as_ptr(), target, key.as_bytes().len());
+        //     self.set_len(self.len()+ key.len() );
+        // };
+        pos
+    }
+
+    #[inline]
+    fn key_equals(&self, pointer: &BytesRef, key: &str) -> bool {
+        self.read(pointer) == key
+    }
+
+    #[inline]
+    fn hash_key<H: StringHasher>(&self, pointer: &BytesRef, hasher: &H) -> u64 {
+        hasher.hash64(self.read(pointer).as_bytes())
+    }
+
+    #[inline]
+    fn key_ptr(&self, pointer: &BytesRef) -> *const u8 {
+        self.as_ptr().wrapping_add(pointer.addr() as usize)
+    }
+
+    #[inline]
+    fn try_reserve(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        Vec::try_reserve(self, additional)
+    }
+
+    #[inline]
+    fn shrink_to_fit(&mut self) {
+        Vec::shrink_to_fit(self);
+    }
+
+    #[inline]
+    fn mem_usage(&self, key_bytes: usize) -> MemUsage {
+        MemUsage {
+            string_data: self.capacity(),
+            string_data_unused: self.capacity() - self.len(),
+            varint_overhead: self.len() - key_bytes,
+            ..MemUsage::default()
+        }
+    }
+}
+
+impl<A: Allocator + Clone> private::ContiguousStorage for Vec<u8, A> {
+    #[inline]
+    fn read<'a>(&'a self, pointer: &'a BytesRef) -> &'a str {
+        read_string(self, *pointer)
+    }
+
+    #[inline]
+    fn read_checked<'a>(&'a self, pointer: &'a BytesRef) -> Option<&'a str> {
+        read_string_checked(self, *pointer)
+    }
+}
+
+/// Length of the first chunk of a pool, each following chunk doubles the length.
+const FIRST_CHUNK_LEN: usize = 4096;
+/// Number of chunks which fit into the address space of `BytesRef`.
+const MAX_CHUNKS: usize = 20;
+
+#[inline]
+fn chunk_len(chunk: usize) -> usize {
+    FIRST_CHUNK_LEN << chunk
+}
+
+/// Returns the chunk of `pos` and the position in the chunk.
+#[inline]
+fn chunk_of(pos: u32) -> (usize, usize) {
+    let blocks = pos as usize / FIRST_CHUNK_LEN + 1;
+    let chunk = (usize::BITS - 1 - blocks.leading_zeros()) as usize;
+    (chunk, pos as usize - FIRST_CHUNK_LEN * ((1 << chunk) - 1))
+}
+
+/// Returns the error for string data which exceeds the address space of `BytesRef`.
+#[cold]
+fn capacity_overflow() -> TryReserveError {
+    // `TryReserveErrorKind` is unstable with the `nightly` feature
+    Vec::<u8>::new().try_reserve(usize::MAX).unwrap_err()
+}
+
+/// String data which never moves a key once it is written.
+///
+/// The chunks are allocated with their final capacity and never grow, a key which doesn't fit
+/// into the last chunk is written to the next one. Chunk `i` starts at position
+/// `FIRST_CHUNK_LEN * (2^i - 1)`, chunks which are skipped by a key longer than them stay empty.
+#[derive(Debug)]
+pub(crate) struct Chunks<A: Allocator + Clone> {
+    chunks: Vec<Vec<u8, A>, A>,
+    /// bytes written, without the unused ends of full chunks
+    len: usize,
+}
+
+impl<A: Allocator + Clone> Chunks<A> {
+    fn new_in(alloc: A) -> Self {
+        Chunks {
+            chunks: Vec::new_in(alloc),
+            len: 0,
+        }
+    }
+
+    /// Returns `Ok` with the last chunk if it has room for `len` bytes, `Err` with the chunk to
+    /// allocate otherwise. The chunk is `MAX_CHUNKS` if the bytes don't fit into any chunk.
+    #[inline]
+    fn chunk_with_room(&self, len: usize) -> Result<usize, usize> {
+        if let Some(last) = self.chunks.last() {
+            let chunk = self.chunks.len() - 1;
+            if chunk_len(chunk) - last.len() >= len {
+                return Ok(chunk);
+            }
+        }
+        let mut chunk = self.chunks.len();
+        while chunk < MAX_CHUNKS && chunk_len(chunk) < len {
+            chunk += 1;
+        }
+        Err(chunk)
+    }
+
+    #[cold]
+    fn push_chunk(&mut self, chunk: usize, data: Vec<u8, A>) {
+        while self.chunks.len() < chunk {
+            self.chunks
+                .push(Vec::new_in(self.chunks.allocator().clone()));
+        }
+        self.chunks.push(data);
+    }
+}
+
+impl<A: Allocator + Clone> private::Storage for Chunks<A> {
+    type Key = BytesRef;
+
+    #[inline]
+    fn insert(&mut self, key: &str) -> BytesRef {
+        let (varint, varint_len) = encode_varint(key.len() as u32);
+        let len = varint_len + key.len();
+        let chunk = match self.chunk_with_room(len) {
+            Ok(chunk) => chunk,
+            Err(chunk) => {
+                assert!(chunk < MAX_CHUNKS, "string data exceeds 4GB");
+                let alloc = self.chunks.allocator().clone();
+                self.push_chunk(chunk, Vec::with_capacity_in(chunk_len(chunk), alloc));
+                chunk
+            }
+        };
+        let data = &mut self.chunks[chunk];
+        let pos = FIRST_CHUNK_LEN * ((1 << chunk) - 1) + data.len();
+        // within the capacity of the chunk, so the bytes of other keys don't move
+        data.extend_from_slice(&varint[..varint_len]);
+        data.extend_from_slice(key.as_bytes());
+        self.len += len;
+        BytesRef(pos as u32)
+    }
+
+    #[inline]
+    fn key_equals(&self, pointer: &BytesRef, key: &str) -> bool {
+        self.read(pointer) == key
+    }
+
+    #[inline]
+    fn hash_key<H: StringHasher>(&self, pointer: &BytesRef, hasher: &H) -> u64 {
+        hasher.hash64(self.read(pointer).as_bytes())
+    }
+
+    #[inline]
+    fn key_ptr(&self, pointer: &BytesRef) -> *const u8 {
+        let (chunk, pos) = chunk_of(pointer.addr());
+        self.chunks
+            .get(chunk)
+            .map_or(core::ptr::null(), |data| data.as_ptr().wrapping_add(pos))
+    }
+
+    #[inline]
+    fn try_reserve(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        if let Err(chunk) = self.chunk_with_room(additional) {
+            if chunk == MAX_CHUNKS {
+                return Err(capacity_overflow());
+            }
+            self.chunks.try_reserve(chunk + 1 - self.chunks.len())?;
+            let mut data = Vec::new_in(self.chunks.allocator().clone());
+            data.try_reserve_exact(chunk_len(chunk))?;
+            self.push_chunk(chunk, data);
+        }
+        Ok(())
+    }
+
+    /// Does nothing, shrinking a chunk would move its keys.
+    #[inline]
+    fn shrink_to_fit(&mut self) {}
+
+    #[inline]
+    fn mem_usage(&self, key_bytes: usize) -> MemUsage {
+        let string_data = self.chunks.iter().map(Vec::capacity).sum::<usize>()
+            + self.chunks.capacity() * core::mem::size_of::<Vec<u8, A>>();
+        MemUsage {
+            string_data,
+            string_data_unused: string_data - self.len,
+            varint_overhead: self.len - key_bytes,
+            ..MemUsage::default()
+        }
+    }
+}
+
+impl<A: Allocator + Clone> private::ContiguousStorage for Chunks<A> {
+    #[inline]
+    fn read<'a>(&'a self, pointer: &'a BytesRef) -> &'a str {
+        let (chunk, pos) = chunk_of(pointer.addr());
+        read_string(
+            unsafe { self.chunks.get_unchecked(chunk) },
+            BytesRef(pos as u32),
+        )
+    }
+
+    #[inline]
+    fn read_checked<'a>(&'a self, pointer: &'a BytesRef) -> Option<&'a str> {
+        let (chunk, pos) = chunk_of(pointer.addr());
+        read_string_checked(self.chunks.get(chunk)?, BytesRef(pos as u32))
+    }
+}
+
+/// Append-only storage of distinct strings shared by several maps, see the module
+/// documentation.
+pub struct StringPool<A: Allocator + Clone = Global> {
+    /// Only borrowed within the methods of the pool, so maps which share the pool don't overlap
+    /// their borrows. References to keys point into the chunks, which are never moved.
+    index: UnsafeCell<StringHashMap<(), A, Yoshimitsu, Chunks<A>>>,
+}
+
+impl Default for StringPool {
+    fn default() -> Self {
+        StringPool::new()
+    }
+}
+
+impl StringPool {
+    #[inline]
+    pub fn new() -> Self {
+        StringPool::new_in(Global)
+    }
+}
+
+impl<A: Allocator + Clone> StringPool<A> {
+    #[inline]
+    pub fn new_in(alloc: A) -> Self {
+        let shift = 10 - 1;
+        let mut table = Vec::with_capacity_in(1 << shift, alloc.clone());
+        table.resize(1 << shift, TableEntry::default());
+        StringPool {
+            index: UnsafeCell::new(StringHashMap::from_parts(
+                table,
+                Chunks::new_in(alloc),
+                10,
+                Yoshimitsu,
+            )),
+        }
+    }
+
+    #[inline]
+    fn index(&self) -> &StringHashMap<(), A, Yoshimitsu, Chunks<A>> {
+        unsafe { &*self.index.get() }
+    }
+
+    #[inline]
+    #[allow(clippy::mut_from_ref)]
+    fn index_mut(&self) -> &mut StringHashMap<(), A, Yoshimitsu, Chunks<A>> {
+        unsafe { &mut *self.index.get() }
+    }
+
+    /// Returns the number of distinct strings.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.index().len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.index().is_empty()
+    }
+
+    /// Returns true if `el` has been inserted into any map of the pool.
+    #[inline]
+    pub fn contains(&self, el: &str) -> bool {
+        self.index().get(el).is_some()
+    }
+
+    /// Returns the memory usage of the pool, `string_data` contains the strings of all maps of
+    /// the pool.
+    #[inline]
+    pub fn mem_usage(&self) -> MemUsage {
+        self.index().mem_usage()
+    }
+
+    #[inline]
+    fn intern(&self, key: &str) -> BytesRef {
+        self.index_mut()
+            .get_or_create_entry(HashedKey::new(key), ())
+            .pointer
+    }
+
+    #[inline]
+    fn read(&self, pointer: BytesRef) -> &str {
+        let key: *const str = self.index().string_data.read(&pointer);
+        // the chunks never move their keys, so the key lives as long as the pool
+        unsafe { &*key }
+    }
+
+    #[inline]
+    fn read_checked(&self, pointer: BytesRef) -> Option<&str> {
+        let key: *const str = self.index().string_data.read_checked(&pointer)?;
+        Some(unsafe { &*key })
+    }
+
+    #[inline]
+    fn key_ptr(&self, pointer: BytesRef) -> *const u8 {
+        self.index().string_data.key_ptr(&pointer)
+    }
+
+    #[inline]
+    fn try_reserve(&self, additional: usize) -> Result<(), TryReserveError> {
+        let index = self.index_mut();
+        index.try_reserve_one()?;
+        index.string_data.try_reserve(additional)
+    }
+}
+
+impl<A: Allocator + Clone> Debug for StringPool<A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StringPool")
+            .field("len", &self.len())
+            .finish()
+    }
+}
+
+macro_rules! impl_storage_for_pool {
+    ($($ty:ty),*) => {
+        $(
+            impl<'p, A: Allocator + Clone> private::Storage for $ty {
+                type Key = BytesRef;
+
+                #[inline]
+                fn insert(&mut self, key: &str) -> BytesRef {
+                    self.intern(key)
+                }
+
+                #[inline]
+                fn key_equals(&self, pointer: &BytesRef, key: &str) -> bool {
+                    StringPool::read(self, *pointer) == key
+                }
+
+                #[inline]
+                fn hash_key<H: StringHasher>(&self, pointer: &BytesRef, hasher: &H) -> u64 {
+                    hasher.hash64(StringPool::read(self, *pointer).as_bytes())
+                }
+
+                #[inline]
+                fn key_ptr(&self, pointer: &BytesRef) -> *const u8 {
+                    StringPool::key_ptr(self, *pointer)
+                }
+
+                #[inline]
+                fn try_reserve(&mut self, additional: usize) -> Result<(), TryReserveError> {
+                    StringPool::try_reserve(self, additional)
+                }
+
+                /// Does nothing, the pool is shared with other maps.
+                #[inline]
+                fn shrink_to_fit(&mut self) {}
+
+                /// The keys belong to the pool, see `StringPool::mem_usage`.
+                #[inline]
+                fn mem_usage(&self, _key_bytes: usize) -> MemUsage {
+                    MemUsage::default()
+                }
+            }
+
+            impl<'p, A: Allocator + Clone> private::ContiguousStorage for $ty {
+                #[inline]
+                fn read<'a>(&'a self, pointer: &'a BytesRef) -> &'a str {
+                    StringPool::read(self, *pointer)
+                }
+
+                #[inline]
+                fn read_checked<'a>(&'a self, pointer: &'a BytesRef) -> Option<&'a str> {
+                    StringPool::read_checked(self, *pointer)
+                }
+            }
+        )*
+    };
+}
+
+/// Pointer to a pool which is owned next to the maps which store their keys in it, see
+/// `NamespacedStringHashMap`.
+pub(crate) struct OwnedPool<A: Allocator + Clone>(NonNull<StringPool<A>>);
+
+impl<A: Allocator + Clone> OwnedPool<A> {
+    /// # Safety
+    /// `pool` has to stay valid and must not be moved while the returned pointer and the keys
+    /// read through it are in use.
+    #[inline]
+    pub(crate) unsafe fn new(pool: NonNull<StringPool<A>>) -> Self {
+        OwnedPool(pool)
+    }
+}
+
+impl<A: Allocator + Clone> Deref for OwnedPool<A> {
+    type Target = StringPool<A>;
+
+    #[inline]
+    fn deref(&self) -> &StringPool<A> {
+        unsafe { self.0.as_ref() }
+    }
+}
+
+impl<A: Allocator + Clone> Debug for OwnedPool<A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Debug::fmt(&**self, f)
+    }
+}
+
+impl_storage_for_pool!(&'p StringPool<A>, Rc<StringPool<A>>, OwnedPool<A>);
+#[cfg(target_has_atomic = "ptr")]
+impl_storage_for_pool!(Arc<StringPool<A>>);
+
+impl<T: Default + Clone + Debug, S: KeyStorage> StringHashMap<T, Global, Yoshimitsu, S> {
+    /// Creates a map which stores its keys in `pool`, e.g. a `&StringPool` shared with other
+    /// maps.
+    #[inline]
+    pub fn with_pool(pool: S) -> Self {
+        Self::with_power_of_two_size_and_pool(10, pool)
+    }
+
+    #[inline]
+    pub fn with_power_of_two_size_and_pool(power_of_two: usize, pool: S) -> Self {
+        let shift = power_of_two - 1;
+        let mut table = Vec::with_capacity(1 << shift);
+        table.resize(1 << shift, TableEntry::default());
+        Self::from_parts(table, pool, power_of_two, Yoshimitsu)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{assert_same_values, test_words};
+    use crate::StringHashMap;
+    use std::vec::Vec;
+
+    type PooledMap<'p, T> = StringHashMap<T, Global, Yoshimitsu, &'p StringPool>;
+
+    #[test]
+    fn maps_share_pool() {
+        let contents = test_words();
+        let pool = StringPool::new();
+        let mut counts = PooledMap::<u32>::with_power_of_two_size_and_pool(1, &pool);
+        let mut lines = PooledMap::<(usize, usize)>::with_power_of_two_size_and_pool(1, &pool);
+        let mut reference_counts = StringHashMap::<u32>::new();
+        let mut reference_lines = StringHashMap::<(usize, usize)>::new();
+        for (line_number, line) in contents.lines().enumerate() {
+            for (i, text) in line.split_whitespace().enumerate() {
+                // the maps get different subsets of the keys
+                if i % 2 == 0 {
+                    *counts.get_or_create(text, 0) += 1;
+                    *reference_counts.get_or_create(text, 0) += 1;
+                }
+                if line_number % 3 == 0 {
+                    lines.get_or_create(text, (line_number, 0)).1 = line_number;
+                    reference_lines.get_or_create(text, (line_number, 0)).1 = line_number;
+                }
+            }
+        }
+        assert_eq!(counts.len(), reference_counts.len());
+        assert_eq!(lines.len(), reference_lines.len());
+        let distinct_keys = reference_counts
+            .keys()
+            .chain(reference_lines.keys())
+            .collect::<std::collections::HashSet<_>>();
+        assert_eq!(pool.len(), distinct_keys.len());
+        assert!(pool.len() < counts.len() + lines.len());
+        // the keys fill several chunks
+        assert!(pool.mem_usage().string_data > FIRST_CHUNK_LEN * 4);
+        assert_eq!(counts.mem_usage().string_data, 0);
+        assert_same_values(&reference_counts, counts.len(), |key| {
+            counts.get(key).copied()
+        });
+        for (key, value) in reference_lines.iter() {
+            assert_eq!(lines.get(key), Some(value));
+        }
+        for (key, value) in counts.iter() {
+            assert_eq!(reference_counts.get(key), Some(value));
+        }
+        assert_eq!(counts.get("not in the pool"), None);
+    }
+
+    #[test]
+    fn key_in_pool_but_not_in_map() {
+        let pool = StringPool::new();
+        let mut first = PooledMap::<u32>::with_pool(&pool);
+        let mut second = PooledMap::<u32>::with_pool(&pool);
+        first.get_or_create("blub", 1);
+        second.get_or_create("bla", 2);
+        assert!(pool.contains("bla"));
+        assert_eq!(first.get("bla"), None);
+        assert_eq!(second.get_mut("blub"), None);
+        *second.get_or_create("blub", 0) += 3;
+        for (_, value) in second.iter_mut() {
+            *value *= 10;
+        }
+        let mut entries = second.iter().collect::<Vec<_>>();
+        entries.sort();
+        assert_eq!(entries, vec![("bla", &20), ("blub", &30)]);
+        assert_eq!(first.get("blub"), Some(&1));
+        assert_eq!(pool.len(), 2);
+    }
+
+    #[test]
+    fn keys_stay_valid_while_pool_grows() {
+        let pool = StringPool::new();
+        let mut first = PooledMap::<u32>::with_pool(&pool);
+        let mut second = PooledMap::<u32>::with_pool(&pool);
+        first.get_or_create("blub", 1);
+        let (key, _) = first.iter().next().unwrap();
+        // longer than several chunks
+        let long_key = "a".repeat(FIRST_CHUNK_LEN * 5);
+        for i in 0..10_000 {
+            second.get_or_create(&format!("blub{}", i), i);
+        }
+        second.get_or_create(&long_key, 1);
+        second.get_or_create("bla", 2);
+        assert_eq!(key, "blub");
+        assert_eq!(second.get(&long_key), Some(&1));
+        assert_eq!(second.get("bla"), Some(&2));
+        assert!(second.iter().any(|(key, _)| key == long_key));
+    }
+
+    #[test]
+    fn shared_with_rc() {
+        let pool = Rc::new(StringPool::new());
+        let mut maps = (0..2)
+            .map(|_| StringHashMap::<u32, _, _, _>::with_pool(Rc::clone(&pool)))
+            .collect::<Vec<_>>();
+        for (i, map) in maps.iter_mut().enumerate() {
+            for key in ["blub", "bla", "blub"].iter() {
+                *map.get_or_create(*key, i as u32) += 1;
+            }
+        }
+        assert_eq!(pool.len(), 2);
+        assert_eq!(maps[0].get("blub"), Some(&2));
+        assert_eq!(maps[1].get("blub"), Some(&3));
+    }
+
+    #[test]
+    fn chunk_positions() {
+        assert_eq!(chunk_of(0), (0, 0));
+        assert_eq!(
+            chunk_of(FIRST_CHUNK_LEN as u32 - 1),
+            (0, FIRST_CHUNK_LEN - 1)
+        );
+        assert_eq!(chunk_of(FIRST_CHUNK_LEN as u32), (1, 0));
+        assert_eq!(chunk_of(FIRST_CHUNK_LEN as u32 * 3), (2, 0));
+        // the last chunk ends before the null pointer
+        assert_eq!(chunk_of(u32::MAX).0, MAX_CHUNKS);
+    }
+
+    #[test]
+    fn try_reserve_beyond_last_chunk() {
+        let mut chunks = Chunks::new_in(Global);
+        assert!(chunks.try_reserve(usize::MAX).is_err());
+        assert!(chunks.try_reserve(10).is_ok());
+        let pointer = chunks.insert("blub");
+        assert_eq!(chunks.read(&pointer), "blub");
+    }
+}
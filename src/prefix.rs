@@ -0,0 +1,496 @@
+/*! Front coded key storage.
+
+`PrefixKeyStorage` stores each key as a shared prefix with one of the recently inserted keys
+plus the remaining suffix. This is a good fit for keys with long common prefixes like URLs or
+file paths, which are inserted close to each other. `PrefixStringHashMap` is a `StringHashMap`
+which stores its keys in a `PrefixKeyStorage`.
+
+Keys are not stored contiguously anymore, so `keys` and `iter` can't borrow them as `&str`, but
+reconstruct them into a `String`. Key comparisons during lookup are done in place without
+reconstruction, hashing a key on resize reconstructs it on the stack.
+
+# Examples
+```
+use inohashmap::PrefixStringHashMap;
+let mut hashmap = PrefixStringHashMap::<u32>::with_prefix_keys();
+*hashmap.get_or_create("https://example.com/blog/1", 0) += 1;
+*hashmap.get_or_create("https://example.com/blog/2", 0) += 1;
+assert_eq!(hashmap.get("https://example.com/blog/2"), Some(&1));
+assert!(hashmap.compression_ratio() < 1.0);
+```
+
+*/
+
+use crate::bytesref::BytesRef;
+use crate::hasher::{StringHasher, Yoshimitsu};
+use crate::pool::private::Storage;
+use crate::vint::{decode_varint_slice, encode_varint_into, MAX_VARINT_LEN};
+use crate::{Global, MemUsage, StringHashMap, TryReserveError};
+use alloc::string::String;
+use allocator_api2::vec::Vec;
+use core::fmt::Debug;
+
+/// Number of recently inserted keys which are considered as prefix source.
+const NUM_RECENT_KEYS: usize = 8;
+/// Maximum number of back references to follow to reconstruct a key.
+const MAX_CHAIN_DEPTH: u8 = 16;
+/// Keys up to this length are reconstructed on the stack to hash them.
+const STACK_KEY_LEN: usize = 128;
+
+/// `StringHashMap` with front coded keys, see the module documentation.
+pub type PrefixStringHashMap<T> = StringHashMap<T, Global, Yoshimitsu, PrefixKeyStorage>;
+
+/// Key storage which shares a prefix with a recently inserted key, see the module
+/// documentation.
+#[derive(Debug, Default)]
+pub struct PrefixKeyStorage {
+    /// contains keys as
+    /// `suffix_len << 1 | has_prefix varint | [prefix_len varint | back reference varint] | suffix`
+    string_data: Vec<u8>,
+    /// ring buffer of the last inserted keys
+    recent: Vec<RecentKey>,
+    recent_pos: usize,
+    /// size the keys would have in a `StringHashMap`
+    uncompressed_bytes: usize,
+    /// bytes spent on the varints in front of the suffixes
+    header_bytes: usize,
+}
+
+#[derive(Debug, Default)]
+struct RecentKey {
+    text: Vec<u8>,
+    pointer: BytesRef,
+    /// number of back references of this key
+    depth: u8,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct KeyHeader {
+    prefix_len: usize,
+    suffix_len: usize,
+    suffix_start: usize,
+    /// position of the key the prefix is shared with
+    base: usize,
+}
+
+impl<T: Default + Clone + Debug> PrefixStringHashMap<T> {
+    /// Creates a map which stores its keys in a new `PrefixKeyStorage`.
+    #[inline]
+    pub fn with_prefix_keys() -> Self {
+        Self::with_power_of_two_size_and_prefix_keys(10)
+    }
+
+    #[inline]
+    pub fn with_power_of_two_size_and_prefix_keys(power_of_two: usize) -> Self {
+        Self::with_power_of_two_size_and_pool(power_of_two, PrefixKeyStorage::new())
+    }
+
+    /// Size of the compressed key data relative to the key data of a `StringHashMap` with the
+    /// same keys.
+    pub fn compression_ratio(&self) -> f32 {
+        self.string_data.compression_ratio()
+    }
+
+    /// Size of the compressed key data in bytes.
+    #[inline]
+    pub fn string_data_len(&self) -> usize {
+        self.string_data.string_data.len()
+    }
+
+    /// Returns the keys in insertion order. Every key is reconstructed into a new `String`.
+    #[inline]
+    pub fn keys(&self) -> impl Iterator<Item = String> + '_ {
+        let string_data = &self.string_data;
+        let mut pos = 0;
+        core::iter::from_fn(move || {
+            if pos == string_data.string_data.len() {
+                return None;
+            }
+            let header = string_data.read_header(pos);
+            let mut text = String::new();
+            string_data.read_key_into(BytesRef(pos as u32), &mut text);
+            pos = header.suffix_start + header.suffix_len;
+            Some(text)
+        })
+    }
+
+    /// Returns all keys with their values. Every key is reconstructed into a new `String`.
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = (String, &T)> {
+        let string_data = &self.string_data;
+        self.table
+            .iter()
+            .chain(
+                self.old_table
+                    .iter()
+                    .flat_map(crate::incremental::OldTable::entries),
+            )
+            .filter(|entry| !entry.pointer.is_null())
+            .map(move |entry| {
+                let mut text = String::new();
+                string_data.read_key_into(entry.pointer, &mut text);
+                (text, &entry.value)
+            })
+    }
+}
+
+impl PrefixKeyStorage {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Size of the compressed key data relative to the key data of a `StringHashMap` with the
+    /// same keys.
+    pub fn compression_ratio(&self) -> f32 {
+        if self.uncompressed_bytes == 0 {
+            return 1.0;
+        }
+        self.string_data.len() as f32 / self.uncompressed_bytes as f32
+    }
+
+    #[inline]
+    fn read_header(&self, mut pos: usize) -> KeyHeader {
+        let start = pos;
+        let suffix_len = decode_varint_slice(&self.string_data, &mut pos).unwrap();
+        let (prefix_len, base) = if suffix_len & 1 == 0 {
+            (0, start)
+        } else {
+            let prefix_len = decode_varint_slice(&self.string_data, &mut pos).unwrap();
+            let back_reference = decode_varint_slice(&self.string_data, &mut pos).unwrap();
+            (prefix_len as usize, start - back_reference as usize)
+        };
+        let suffix_len = (suffix_len >> 1) as usize;
+        KeyHeader {
+            prefix_len,
+            suffix_len,
+            suffix_start: pos,
+            base,
+        }
+    }
+
+    /// Fills `out`, which has the length of the key of `header`, with the key.
+    fn copy_key(&self, mut header: KeyHeader, out: &mut [u8]) {
+        let mut end = out.len();
+        // fill the key from the back, every back reference provides the bytes before its suffix
+        loop {
+            if end > header.prefix_len {
+                let suffix_end = header.suffix_start + end - header.prefix_len;
+                out[header.prefix_len..end]
+                    .copy_from_slice(&self.string_data[header.suffix_start..suffix_end]);
+                end = header.prefix_len;
+            }
+            if end == 0 {
+                return;
+            }
+            header = self.read_header(header.base);
+        }
+    }
+
+    /// Reconstructs the key at `pos` into `out`.
+    pub(crate) fn read_key_into(&self, pos: BytesRef, out: &mut String) {
+        let header = self.read_header(pos.addr() as usize);
+        let mut bytes = core::mem::take(out).into_bytes();
+        bytes.clear();
+        bytes.resize(header.prefix_len + header.suffix_len, 0);
+        self.copy_key(header, &mut bytes);
+        // the reconstructed bytes are identical to the inserted key
+        *out = unsafe { String::from_utf8_unchecked(bytes) };
+    }
+}
+
+impl Storage for PrefixKeyStorage {
+    type Key = BytesRef;
+
+    /// Appends the key to the string data, sharing a prefix with one of the recent keys.
+    fn insert(&mut self, key: &str) -> BytesRef {
+        let pos = self.string_data.len();
+        let bytes = key.as_bytes();
+
+        // pick the recent key which saves the most bytes, including the back reference overhead
+        let mut best: Option<(usize, usize, usize)> = None;
+        for (recent_pos, recent) in self.recent.iter().enumerate() {
+            // `try_reserve` may have added an empty slot
+            if recent.depth >= MAX_CHAIN_DEPTH || recent.pointer.is_null() {
+                continue;
+            }
+            let common = common_prefix_len(&recent.text, bytes);
+            let overhead =
+                varint_len(common as u32) + varint_len(pos as u32 - recent.pointer.addr());
+            if common > overhead
+                && best
+                    .map(|(_, _, saved)| common - overhead > saved)
+                    .unwrap_or(true)
+            {
+                best = Some((recent_pos, common, common - overhead));
+            }
+        }
+
+        let (prefix_len, depth) = match best {
+            Some((recent_pos, prefix_len, _)) => {
+                let base = &self.recent[recent_pos];
+                let suffix_len = (bytes.len() - prefix_len) as u32;
+                encode_varint_into(&mut self.string_data, suffix_len << 1 | 1);
+                encode_varint_into(&mut self.string_data, prefix_len as u32);
+                encode_varint_into(&mut self.string_data, pos as u32 - base.pointer.addr());
+                (prefix_len, base.depth + 1)
+            }
+            None => {
+                encode_varint_into(&mut self.string_data, (bytes.len() as u32) << 1);
+                (0, 0)
+            }
+        };
+        self.header_bytes += self.string_data.len() - pos;
+        self.string_data.extend_from_slice(&bytes[prefix_len..]);
+        self.uncompressed_bytes += varint_len(bytes.len() as u32) + bytes.len();
+
+        let pointer = BytesRef(pos as u32);
+        if self.recent_pos == self.recent.len() {
+            self.recent.push(RecentKey::default());
+        }
+        let recent = &mut self.recent[self.recent_pos];
+        recent.text.clear();
+        recent.text.extend_from_slice(bytes);
+        recent.pointer = pointer;
+        recent.depth = depth;
+        self.recent_pos = (self.recent_pos + 1) % NUM_RECENT_KEYS;
+
+        pointer
+    }
+
+    /// Compares the key at `pointer` with `key` without reconstructing it.
+    #[inline]
+    fn key_equals(&self, pointer: &BytesRef, key: &str) -> bool {
+        let mut el = key.as_bytes();
+        let mut header = self.read_header(pointer.addr() as usize);
+        if header.prefix_len + header.suffix_len != el.len() {
+            return false;
+        }
+        // walk the back references, every step checks the part of `el` after the shared prefix
+        loop {
+            if el.len() > header.prefix_len {
+                let suffix_end = header.suffix_start + el.len() - header.prefix_len;
+                if self.string_data[header.suffix_start..suffix_end] != el[header.prefix_len..] {
+                    return false;
+                }
+                el = &el[..header.prefix_len];
+            }
+            if el.is_empty() {
+                return true;
+            }
+            header = self.read_header(header.base);
+        }
+    }
+
+    #[inline]
+    fn hash_key<H: StringHasher>(&self, pointer: &BytesRef, hasher: &H) -> u64 {
+        let header = self.read_header(pointer.addr() as usize);
+        let len = header.prefix_len + header.suffix_len;
+        if len <= STACK_KEY_LEN {
+            let mut buf = [0u8; STACK_KEY_LEN];
+            self.copy_key(header, &mut buf[..len]);
+            hasher.hash64(&buf[..len])
+        } else {
+            let mut buf = alloc::vec![0u8; len];
+            self.copy_key(header, &mut buf);
+            hasher.hash64(&buf)
+        }
+    }
+
+    #[inline]
+    fn key_ptr(&self, pointer: &BytesRef) -> *const u8 {
+        self.string_data
+            .as_ptr()
+            .wrapping_add(pointer.addr() as usize)
+    }
+
+    /// Reserves room for a key of `additional` bytes, including the back reference and the copy
+    /// in the recent keys.
+    #[inline]
+    fn try_reserve(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        self.string_data
+            .try_reserve(additional + 2 * MAX_VARINT_LEN)?;
+        if self.recent_pos == self.recent.len() {
+            self.recent.try_reserve(1)?;
+            self.recent.push(RecentKey::default());
+        }
+        self.recent[self.recent_pos].text.try_reserve(additional)
+    }
+
+    #[inline]
+    fn shrink_to_fit(&mut self) {
+        self.string_data.shrink_to_fit();
+    }
+
+    #[inline]
+    fn mem_usage(&self, _key_bytes: usize) -> MemUsage {
+        MemUsage {
+            string_data: self.string_data.capacity(),
+            string_data_unused: self.string_data.capacity() - self.string_data.len(),
+            varint_overhead: self.header_bytes,
+            ..MemUsage::default()
+        }
+    }
+}
+
+#[inline]
+fn common_prefix_len(a: &[u8], b: &[u8]) -> usize {
+    a.iter().zip(b.iter()).take_while(|(a, b)| a == b).count()
+}
+
+#[inline]
+fn varint_len(value: u32) -> usize {
+    match value {
+        0..=0x7F => 1,
+        0x80..=0x3FFF => 2,
+        0x4000..=0x1F_FFFF => 3,
+        0x20_0000..=0xFFF_FFFF => 4,
+        _ => 5,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{assert_same_values, test_words};
+    use crate::{HashedKey, StringHashMap};
+    use std::vec::Vec;
+
+    fn url_dataset() -> Vec<String> {
+        let hosts = [
+            "https://www.example.com",
+            "https://docs.rs",
+            "http://blog.example.org",
+        ];
+        let sections = [
+            "articles",
+            "static/images",
+            "api/v1/users",
+            "crates/inohashmap",
+        ];
+        let mut urls = vec![];
+        for host in hosts.iter() {
+            for section in sections.iter() {
+                for id in 0..200 {
+                    urls.push(format!(
+                        "{}/{}/{}/index.html?page={}",
+                        host,
+                        section,
+                        id,
+                        id % 7
+                    ));
+                }
+            }
+        }
+        urls
+    }
+
+    fn check_against_stringhashmap<'a>(keys: impl Iterator<Item = &'a str>) -> f32 {
+        let mut map = PrefixStringHashMap::<u32>::with_prefix_keys();
+        let mut reference = StringHashMap::<u32>::new();
+        for key in keys {
+            *map.get_or_create(key, 0) += 1;
+            *reference.get_or_create(key, 0) += 1;
+        }
+        assert_same_values(&reference, map.len(), |key| map.get(key).copied());
+        assert_eq!(
+            map.keys().collect::<Vec<_>>(),
+            reference.keys().collect::<Vec<_>>()
+        );
+        assert_eq!(
+            map.string_data.uncompressed_bytes,
+            reference.string_data.len()
+        );
+        map.compression_ratio()
+    }
+
+    #[test]
+    fn compression_ratio_text() {
+        let contents = test_words();
+        let ratio = check_against_stringhashmap(contents.split_whitespace());
+        assert!(ratio > 0.95 && ratio < 1.0, "{}", ratio);
+    }
+
+    #[test]
+    fn compression_ratio_urls() {
+        let urls = url_dataset();
+        let ratio = check_against_stringhashmap(urls.iter().map(|url| url.as_str()));
+        assert!(ratio > 0.3 && ratio < 0.45, "{}", ratio);
+    }
+
+    #[test]
+    fn get_and_iter() {
+        let mut hashmap = PrefixStringHashMap::<u32>::with_power_of_two_size_and_prefix_keys(1);
+        hashmap.get_or_create("/usr/share/doc", 1);
+        hashmap.get_or_create("/usr/share/man", 2);
+        hashmap.get_or_create("/usr/share", 3);
+        hashmap.get_or_create("/usr/share/man/man1", 4);
+        hashmap.get_or_create("", 5);
+
+        assert_eq!(hashmap.len(), 5);
+        assert_eq!(hashmap.get("/usr/share/doc"), Some(&1));
+        assert_eq!(hashmap.get("/usr/share/man"), Some(&2));
+        assert_eq!(hashmap.get("/usr/share"), Some(&3));
+        assert_eq!(hashmap.get_mut("/usr/share/man/man1"), Some(&mut 4));
+        assert_eq!(hashmap.get(""), Some(&5));
+        assert_eq!(hashmap.get("/usr/share/ma"), None);
+        assert_eq!(hashmap.get("/usr/share/mab"), None);
+        assert_eq!(hashmap.get_or_create("/usr/share/man", 0), &2);
+
+        assert_eq!(
+            hashmap.keys().collect::<Vec<_>>(),
+            &[
+                "/usr/share/doc",
+                "/usr/share/man",
+                "/usr/share",
+                "/usr/share/man/man1",
+                ""
+            ]
+        );
+        let mut entries = hashmap.iter().collect::<Vec<_>>();
+        entries.sort();
+        assert_eq!(entries[0], ("".to_string(), &5));
+        assert_eq!(entries[1], ("/usr/share".to_string(), &3));
+        assert_eq!(hashmap.values().sum::<u32>(), 15);
+    }
+
+    #[test]
+    fn incremental_resize_and_try_get_or_create() {
+        let urls = url_dataset();
+        let mut hashmap = PrefixStringHashMap::<usize>::with_power_of_two_size_and_prefix_keys(1);
+        hashmap.set_incremental_resize(true);
+        for (i, url) in urls.iter().enumerate() {
+            *hashmap.try_get_or_create(url.as_str(), i).unwrap() += 1;
+        }
+        assert_eq!(hashmap.len(), urls.len());
+        for (i, url) in urls.iter().enumerate() {
+            assert_eq!(hashmap.get(url.as_str()), Some(&(i + 1)));
+        }
+        let stats = hashmap.stats();
+        assert_eq!(stats.successful_probes.iter().sum::<usize>(), urls.len());
+        let mem_usage = hashmap.mem_usage();
+        assert_eq!(
+            mem_usage.string_data,
+            hashmap.string_data.string_data.capacity()
+        );
+        assert!(mem_usage.varint_overhead < hashmap.string_data_len());
+    }
+
+    #[test]
+    fn long_chains() {
+        let mut hashmap = PrefixStringHashMap::<usize>::with_prefix_keys();
+        let keys = (0..1000)
+            .map(|i| format!("prefix/{}", "a".repeat(i % 50)))
+            .collect::<Vec<_>>();
+        for (i, key) in keys.iter().enumerate().take(50) {
+            hashmap.get_or_create(key, i);
+        }
+        for (i, key) in keys.iter().enumerate().take(50) {
+            assert_eq!(hashmap.get(key), Some(&i));
+            let mut text = String::new();
+            let pointer = hashmap.find_entry(HashedKey::new(key)).unwrap().pointer;
+            hashmap.string_data.read_key_into(pointer, &mut text);
+            assert_eq!(&text, key);
+        }
+    }
+}
@@ -0,0 +1,215 @@
+//! Protection against keys which are chosen to collide, see `StringHashMap::set_max_probe_len`.
+
+use crate::hasher::{SipHash, StringHasher};
+use crate::{Allocator, Global, HashedKey, KeyStorage, StringHashMap, TableEntry, TryReserveError};
+use allocator_api2::vec::Vec;
+use core::fmt::Debug;
+
+impl<T: Default + Clone + Debug> StringHashMap<T, Global, SipHash> {
+    /// Creates a map which hashes keys with `SipHash` and random keys, so the buckets of keys
+    /// can't be predicted from outside.
+    ///
+    /// # Examples
+    /// ```
+    /// use inohashmap::StringHashMap;
+    /// let mut hashmap = StringHashMap::<u32, _, _>::with_random_seed();
+    /// hashmap.get_or_create("blub", 1);
+    /// assert_eq!(hashmap.get("blub"), Some(&1));
+    /// ```
+    #[cfg(feature = "std")]
+    #[inline]
+    pub fn with_random_seed() -> Self {
+        Self::with_hasher(SipHash::random())
+    }
+}
+
+impl<T: Default + Clone + Debug, A: Allocator + Clone, H: StringHasher, S: KeyStorage>
+    StringHashMap<T, A, H, S>
+{
+    /// Re-seeds the hasher and rehashes the table, when inserting a key needs more than
+    /// `max_probe_len` probes. `None`, the default, disables the check.
+    ///
+    /// Long probe sequences are a sign of keys chosen to collide, e.g. by a user who knows the
+    /// hash function. This requires a hasher which supports `StringHasher::reseed`, like
+    /// `SipHash`, otherwise the check has no effect. To avoid rehashing over and over when the
+    /// probe sequences are long for other reasons, the hasher is re-seeded at most once per
+    /// table size.
+    ///
+    /// After a reseed `HashedKey`s created with the previous seed must not be used anymore.
+    #[inline]
+    pub fn set_max_probe_len(&mut self, max_probe_len: Option<usize>) {
+        self.max_probe_len = max_probe_len;
+    }
+
+    /// Returns the number of times the hasher has been re-seeded.
+    #[inline]
+    pub fn num_reseeds(&self) -> usize {
+        self.num_reseeds
+    }
+
+    /// Checks the probe length to the empty `bucket` for `el`, and returns the bucket to insert
+    /// `el` into, which changes if the table is rehashed.
+    #[inline]
+    pub(crate) fn check_probe_len(&mut self, el: HashedKey<H>, bucket: usize) -> usize {
+        match self.reseeded_hasher(el, bucket) {
+            Some(hasher) => {
+                let table = self.new_table(self.table.len());
+                self.rehash_with(el.key(), hasher, table)
+            }
+            None => bucket,
+        }
+    }
+
+    /// Like `check_probe_len`, but returns an error instead of aborting when the allocator fails.
+    #[inline]
+    pub(crate) fn try_check_probe_len(
+        &mut self,
+        el: HashedKey<H>,
+        bucket: usize,
+    ) -> Result<usize, TryReserveError> {
+        match self.reseeded_hasher(el, bucket) {
+            Some(hasher) => {
+                let table = self.try_new_table(self.table.len())?;
+                Ok(self.rehash_with(el.key(), hasher, table))
+            }
+            None => Ok(bucket),
+        }
+    }
+
+    /// Returns a re-seeded copy of the hasher, if inserting `el` into the empty `bucket` needs
+    /// more than `max_probe_len` probes and the table hasn't been re-seeded at its size yet.
+    #[inline]
+    fn reseeded_hasher(&mut self, el: HashedKey<H>, bucket: usize) -> Option<H> {
+        let max_probe_len = self
+            .max_probe_len
+            .filter(|_| self.reseeded_at != self.table.len())?;
+        let mut probe = self.get_probe(el.hash64());
+        if (0..max_probe_len).any(|_| probe.next_probe() == bucket) {
+            return None;
+        }
+        let mut hasher = self.hasher.clone();
+        if hasher.reseed() {
+            Some(hasher)
+        } else {
+            self.reseeded_at = self.table.len();
+            None
+        }
+    }
+
+    /// Switches to `hasher` and rehashes the table into `table`, which has the size of the
+    /// current table. Returns the empty bucket for `key`.
+    #[cold]
+    fn rehash_with(&mut self, key: &str, hasher: H, table: Vec<TableEntry<T, S::Key>, A>) -> usize {
+        self.hasher = hasher;
+        self.reseeded_at = self.table.len();
+        self.num_reseeds += 1;
+        self.rehash_into(table);
+        let el = self.hash_key(key);
+        self.probe_bucket(el).unwrap_err()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::hasher::{SipHash, StringHasher};
+    use crate::tests::LimitedAlloc;
+    use crate::{HashedKey, StringHashMap};
+    use std::vec::Vec;
+
+    /// Finds keys whose hashes start with the same `bits` bits, like an attacker who knows the
+    /// seed.
+    fn colliding_keys(hasher: &SipHash, bits: u32, num_keys: usize) -> Vec<String> {
+        let target = hasher.hash(b"0") >> (32 - bits);
+        (0..)
+            .map(|i: u64| i.to_string())
+            .filter(|key| hasher.hash(key.as_bytes()) >> (32 - bits) == target)
+            .take(num_keys)
+            .collect()
+    }
+
+    #[test]
+    fn reseed_on_long_probes() {
+        let hasher = SipHash::with_keys(1, 2);
+        let keys = colliding_keys(&hasher, 10, 40);
+
+        let mut hashmap = StringHashMap::<u32, _, _>::with_power_of_two_size_and_hasher(8, hasher);
+        hashmap.set_max_probe_len(Some(16));
+        let key = hashmap.hash_key("blub");
+        for (i, key) in keys.iter().enumerate() {
+            hashmap.get_or_create(key, i as u32);
+        }
+        assert_eq!(hashmap.num_reseeds(), 1);
+        assert_eq!(hashmap.stats().num_reseeds, 1);
+        assert_ne!(hashmap.hash_key("blub"), key);
+        assert!(hashmap.stats().successful_probes.len() <= 16);
+        for (i, key) in keys.iter().enumerate() {
+            assert_eq!(hashmap.get(key), Some(&(i as u32)));
+        }
+        assert_eq!(hashmap.len(), 40);
+    }
+
+    #[test]
+    fn batch_across_reseed() {
+        let hasher = SipHash::with_keys(1, 2);
+        let keys = colliding_keys(&hasher, 10, 40);
+        let keys = keys.iter().map(|key| key.as_str()).collect::<Vec<_>>();
+
+        let mut hashmap = StringHashMap::<u32, _, _>::with_power_of_two_size_and_hasher(8, hasher);
+        hashmap.set_max_probe_len(Some(16));
+        hashmap.get_or_create_batch(&keys, || 0, |pos, value| *value = pos as u32);
+        assert_eq!(hashmap.num_reseeds(), 1);
+        for (i, key) in keys.iter().enumerate() {
+            assert_eq!(hashmap.get(*key), Some(&(i as u32)));
+        }
+        assert_eq!(hashmap.len(), 40);
+    }
+
+    #[test]
+    fn reseed_allocation_error() {
+        let hasher = SipHash::with_keys(1, 2);
+        let keys = colliding_keys(&hasher, 10, 40);
+
+        // room for the table and the string data, but not for a second table to rehash into
+        let alloc = LimitedAlloc {
+            allocated: Default::default(),
+            limit: 1024 + 512,
+        };
+        let mut hashmap = StringHashMap::<u32, _, _>::with_power_of_two_size_and_hasher_in(
+            8,
+            hasher,
+            alloc.clone(),
+        );
+        hashmap.set_max_probe_len(Some(16));
+        hashmap.string_data.reserve_exact(512 - 256);
+        let num_inserted = keys
+            .iter()
+            .take_while(|key| hashmap.try_get_or_create(key.as_str(), 0).is_ok())
+            .count();
+        assert!(num_inserted < keys.len());
+        assert_eq!(hashmap.num_reseeds(), 0);
+        assert_eq!(hashmap.len(), num_inserted);
+        for key in &keys[..num_inserted] {
+            assert_eq!(hashmap.get(key.as_str()), Some(&0));
+        }
+        assert!(alloc.allocated.get() <= 1024 + 512);
+    }
+
+    #[test]
+    fn no_reseed_without_seed() {
+        let mut hashmap = StringHashMap::<u32>::with_power_of_two_size(1);
+        hashmap.set_max_probe_len(Some(1));
+        for i in 0..1000 {
+            hashmap.get_or_create(&i.to_string(), i);
+        }
+        assert_eq!(hashmap.num_reseeds(), 0);
+        assert_eq!(hashmap.get(HashedKey::new("999")), Some(&999));
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn random_seed() {
+        let first = StringHashMap::<u32, _, _>::with_random_seed();
+        let second = StringHashMap::<u32, _, _>::with_random_seed();
+        assert_ne!(first.hash_key("blub"), second.hash_key("blub"));
+    }
+}
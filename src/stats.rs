@@ -0,0 +1,237 @@
+use crate::hasher::StringHasher;
+use crate::pool::private::KeySlot;
+use crate::{Allocator, KeyStorage, QuadraticProbing, StringHashMap};
+use alloc::vec::Vec;
+use core::fmt::Debug;
+
+/// Diagnostic statistics about the table of a `StringHashMap`, see `StringHashMap::stats`.
+///
+/// Histograms are indexed by length minus one, e.g. `successful_probes[0]` is the number of keys
+/// found with the first probe.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Stats {
+    /// Number of keys in the map.
+    pub len: usize,
+    /// Number of buckets in the table.
+    pub num_buckets: usize,
+    /// `len / num_buckets`
+    pub load_factor: f32,
+    /// Number of times the table has been resized.
+    pub num_resizes: usize,
+    /// Number of times the hasher has been re-seeded, see `StringHashMap::set_max_probe_len`.
+    pub num_reseeds: usize,
+    /// Average length of a key in bytes.
+    pub avg_key_len: f32,
+    /// Probe lengths of lookups for every key in the map. During an incremental resize the
+    /// lookup of a key which is still in the old table counts the probes in both tables.
+    pub successful_probes: Vec<usize>,
+    /// Probe lengths of lookups for keys not in the map, over all possible start positions of
+    /// the new table.
+    pub failed_probes: Vec<usize>,
+    /// Lengths of runs of consecutive occupied buckets of the new table.
+    pub cluster_sizes: Vec<usize>,
+}
+
+impl Stats {
+    /// Average number of probes to find a key which is in the map.
+    pub fn avg_successful_probes(&self) -> f32 {
+        histogram_avg(&self.successful_probes)
+    }
+
+    /// Average number of probes to find out that a key is not in the map.
+    pub fn avg_failed_probes(&self) -> f32 {
+        histogram_avg(&self.failed_probes)
+    }
+
+    /// Average size of a cluster of occupied buckets.
+    pub fn avg_cluster_size(&self) -> f32 {
+        histogram_avg(&self.cluster_sizes)
+    }
+}
+
+fn histogram_avg(histogram: &[usize]) -> f32 {
+    let count: usize = histogram.iter().sum();
+    if count == 0 {
+        return 0.0;
+    }
+    let sum: usize = histogram
+        .iter()
+        .enumerate()
+        .map(|(pos, num)| (pos + 1) * num)
+        .sum();
+    sum as f32 / count as f32
+}
+
+fn add_to_histogram(histogram: &mut Vec<usize>, len: usize) {
+    if histogram.len() < len {
+        histogram.resize(len, 0);
+    }
+    histogram[len - 1] += 1;
+}
+
+impl<T: Default + Clone + Debug, A: Allocator + Clone, H: StringHasher, S: KeyStorage>
+    StringHashMap<T, A, H, S>
+{
+    /// Computes diagnostic statistics about the table.
+    ///
+    /// This walks the whole table and replays lookups, so it is meant for diagnostics and
+    /// tuning, not for the hot path.
+    pub fn stats(&self) -> Stats {
+        let mut stats = Stats {
+            len: self.len(),
+            num_buckets: self.table.len(),
+            load_factor: self.len() as f32 / self.table.len() as f32,
+            num_resizes: self.num_resizes,
+            num_reseeds: self.num_reseeds,
+            avg_key_len: if self.is_empty() {
+                0.0
+            } else {
+                self.key_bytes as f32 / self.len() as f32
+            },
+            ..Default::default()
+        };
+
+        for (bucket, entry) in self.table.iter().enumerate() {
+            if entry.pointer.is_null() {
+                continue;
+            }
+            let hash = self.string_data.hash_key(&entry.pointer, &self.hasher);
+            add_to_histogram(
+                &mut stats.successful_probes,
+                probes_to(self.get_probe(hash), bucket),
+            );
+        }
+        if let Some(old) = &self.old_table {
+            let old_mask = old.table.len() - 1;
+            for (bucket, entry) in old.live_entries() {
+                let hash = self.string_data.hash_key(&entry.pointer, &self.hasher);
+                // the lookup misses in the new table first
+                let old_probe =
+                    QuadraticProbing::compute((hash >> (self.bitshift + 1)) as usize, old_mask);
+                add_to_histogram(
+                    &mut stats.successful_probes,
+                    self.probes_to_empty(self.get_probe(hash)) + probes_to(old_probe, bucket),
+                );
+            }
+        }
+
+        // every possible start position of a probe sequence is equally likely for unknown keys
+        let num_start_positions = 1usize << (64 - self.bitshift);
+        for hash in 0..num_start_positions {
+            add_to_histogram(
+                &mut stats.failed_probes,
+                self.probes_to_empty(QuadraticProbing::compute(hash, self.mask)),
+            );
+        }
+
+        let mut cluster_size = 0;
+        for entry in self.table.iter() {
+            if entry.pointer.is_null() {
+                if cluster_size != 0 {
+                    add_to_histogram(&mut stats.cluster_sizes, cluster_size);
+                }
+                cluster_size = 0;
+            } else {
+                cluster_size += 1;
+            }
+        }
+        if cluster_size != 0 {
+            add_to_histogram(&mut stats.cluster_sizes, cluster_size);
+        }
+
+        stats
+    }
+
+    /// Returns the number of probes of `probe` up to the first empty bucket of the table.
+    fn probes_to_empty(&self, mut probe: QuadraticProbing) -> usize {
+        let mut num_probes = 1;
+        // small tables can be completely full, the probe sequence visits every bucket once
+        while num_probes < self.table.len() && !self.get_entry(probe.next_probe()).pointer.is_null()
+        {
+            num_probes += 1;
+        }
+        num_probes
+    }
+}
+
+/// Returns the number of probes of `probe` up to `bucket`.
+fn probes_to(mut probe: QuadraticProbing, bucket: usize) -> usize {
+    let mut num_probes = 1;
+    while probe.next_probe() != bucket {
+        num_probes += 1;
+    }
+    num_probes
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::StringHashMap;
+
+    #[test]
+    fn stats_empty() {
+        let hashmap = StringHashMap::<u32>::new();
+        let stats = hashmap.stats();
+        assert_eq!(stats.len, 0);
+        assert_eq!(stats.num_buckets, 512);
+        assert_eq!(stats.load_factor, 0.0);
+        assert_eq!(stats.avg_key_len, 0.0);
+        assert!(stats.successful_probes.is_empty());
+        assert_eq!(stats.failed_probes, vec![1024]);
+        assert!(stats.cluster_sizes.is_empty());
+    }
+
+    #[test]
+    fn stats_after_resize() {
+        let mut hashmap = StringHashMap::<u32>::with_power_of_two_size(1);
+        hashmap.get_or_create("blub1", 3);
+        hashmap.get_or_create("blub2", 4);
+        hashmap.get_or_create("blub3", 5);
+
+        let stats = hashmap.stats();
+        assert_eq!(stats.len, 3);
+        assert_eq!(stats.num_resizes, 2);
+        assert_eq!(stats.num_buckets, 4);
+        assert_eq!(stats.load_factor, 0.75);
+        assert_eq!(stats.avg_key_len, 5.0);
+        assert_eq!(stats.successful_probes.iter().sum::<usize>(), 3);
+        assert_eq!(stats.failed_probes.iter().sum::<usize>(), 8);
+        assert_eq!(
+            stats
+                .cluster_sizes
+                .iter()
+                .enumerate()
+                .map(|(pos, num)| (pos + 1) * num)
+                .sum::<usize>(),
+            3
+        );
+        assert!(stats.avg_successful_probes() >= 1.0);
+        assert!(stats.avg_failed_probes() > 1.0);
+    }
+
+    #[test]
+    fn stats_during_incremental_resize() {
+        let mut hashmap = StringHashMap::<u32>::with_power_of_two_size(4);
+        hashmap.set_incremental_resize(true);
+        let mut num_keys = 0;
+        while num_keys < 100 || !hashmap.is_resizing() {
+            hashmap.get_or_create(&num_keys.to_string(), num_keys);
+            num_keys += 1;
+        }
+        let stats = hashmap.stats();
+        assert_eq!(
+            stats.successful_probes.iter().sum::<usize>(),
+            num_keys as usize
+        );
+
+        // keys in the old table need at least one probe in each table
+        let num_in_old = hashmap.old_table.as_ref().unwrap().live_entries().count();
+        assert!(num_in_old > 0);
+        assert!(stats.successful_probes[1..].iter().sum::<usize>() >= num_in_old);
+
+        hashmap.finish_resize();
+        assert_eq!(
+            hashmap.stats().successful_probes.iter().sum::<usize>(),
+            num_keys as usize
+        );
+    }
+}
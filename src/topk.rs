@@ -0,0 +1,393 @@
+/*! Most frequent keys.
+
+`StringHashMap::top_k_by` selects the `k` largest values of a map with a bounded heap, without
+collecting all entries.
+
+`HeavyHitters` finds the most frequent keys of a stream with an unbounded number of distinct keys
+in bounded memory, with the Space-Saving algorithm. It monitors a fixed number of keys, and a key
+which is not monitored replaces the one with the smallest count.
+
+# Examples
+```
+use inohashmap::{HeavyHitters, StringHashMap};
+let text = "a b a c a b d";
+let mut counts = StringHashMap::<u32>::new();
+let mut heavy_hitters = HeavyHitters::new(3);
+for term in text.split_whitespace() {
+    *counts.get_or_create(term, 0) += 1;
+    heavy_hitters.add(term, 1);
+}
+assert_eq!(counts.top_k_by(2, |count| *count), vec![("a", &3), ("b", &2)]);
+assert_eq!(heavy_hitters.top(1)[0].key, "a");
+```
+
+*/
+
+use crate::hasher::StringHasher;
+use crate::{Allocator, ContiguousKeyStorage, DenseStringHashMap, KeyHandle, StringHashMap};
+use alloc::collections::BinaryHeap;
+use core::cmp::{Ordering, Reverse};
+use core::fmt::Debug;
+
+/// Entry of the heap of `top_k_by`, ordered by `key` only.
+struct Candidate<K, E> {
+    key: Reverse<K>,
+    /// position in the table, keeps the first entries of equal keys
+    pos: usize,
+    entry: E,
+}
+
+impl<K: Ord, E> PartialEq for Candidate<K, E> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+impl<K: Ord, E> Eq for Candidate<K, E> {}
+impl<K: Ord, E> PartialOrd for Candidate<K, E> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl<K: Ord, E> Ord for Candidate<K, E> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (&self.key, self.pos).cmp(&(&other.key, other.pos))
+    }
+}
+
+impl<
+        T: Default + Clone + Debug,
+        A: Allocator + Clone,
+        H: StringHasher,
+        S: ContiguousKeyStorage,
+    > StringHashMap<T, A, H, S>
+{
+    /// Returns the `k` entries with the largest `key(value)`, largest first. Entries with equal
+    /// keys are returned in table order.
+    ///
+    /// Keeps a heap of `k` entries, so the memory doesn't depend on the size of the map.
+    pub fn top_k_by<K: Ord, F: FnMut(&T) -> K>(
+        &self,
+        k: usize,
+        mut key: F,
+    ) -> alloc::vec::Vec<(&str, &T)> {
+        if k == 0 {
+            return alloc::vec::Vec::new();
+        }
+        // the smallest candidate is on top
+        let mut heap = BinaryHeap::with_capacity(k + 1);
+        for (pos, entry) in self.iter().enumerate() {
+            let candidate = Candidate {
+                key: Reverse(key(entry.1)),
+                pos,
+                entry,
+            };
+            if heap.len() < k {
+                heap.push(candidate);
+            } else {
+                let mut top = heap.peek_mut().unwrap();
+                if candidate < *top {
+                    *top = candidate;
+                }
+            }
+        }
+        heap.into_sorted_vec()
+            .into_iter()
+            .map(|candidate| candidate.entry)
+            .collect()
+    }
+}
+
+/// The index of monitored keys is rebuilt when it has this many keys per monitored key.
+const REBUILD_FACTOR: usize = 8;
+
+/// Frequent keys of a stream, see the module documentation.
+#[derive(Debug)]
+pub struct HeavyHitters {
+    /// slot of every key seen since the last rebuild, slots of evicted keys are stale
+    index: DenseStringHashMap<u32>,
+    slots: alloc::vec::Vec<Slot>,
+    /// min heap of the slots by count
+    heap: alloc::vec::Vec<u32>,
+    capacity: usize,
+    total: u64,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Slot {
+    handle: KeyHandle,
+    count: u64,
+    error: u64,
+    /// position in the heap
+    heap_pos: u32,
+}
+
+/// A monitored key of `HeavyHitters`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HeavyHitter<'a> {
+    pub key: &'a str,
+    /// Upper bound of the number of occurrences.
+    pub count: u64,
+    /// Maximum overestimation of `count`, the key occurred at least `count - error` times.
+    pub error: u64,
+}
+
+impl HeavyHitters {
+    /// Monitors at most `capacity` keys. Every key which makes up more than `1 / capacity` of
+    /// the total weight is monitored.
+    pub fn new(capacity: usize) -> Self {
+        assert!(capacity > 0, "capacity has to be at least 1");
+        HeavyHitters {
+            index: DenseStringHashMap::with_power_of_two_size(1),
+            slots: alloc::vec::Vec::with_capacity(capacity),
+            heap: alloc::vec::Vec::with_capacity(capacity),
+            capacity,
+            total: 0,
+        }
+    }
+
+    /// Returns the number of monitored keys.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.slots.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+
+    /// Returns the sum of all weights added.
+    #[inline]
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    /// Adds `weight` occurrences of `el`.
+    pub fn add(&mut self, el: &str, weight: u64) {
+        self.total += weight;
+        let (handle, slot) = self.index.get_or_create_handle(el, u32::MAX);
+        let slot_id = *slot as usize;
+        if slot_id < self.slots.len() && self.slots[slot_id].handle == handle {
+            self.slots[slot_id].count += weight;
+        } else if self.slots.len() < self.capacity {
+            *slot = self.slots.len() as u32;
+            self.heap.push(*slot);
+            self.slots.push(Slot {
+                handle,
+                count: weight,
+                error: 0,
+                heap_pos: self.heap.len() as u32 - 1,
+            });
+            self.sift_up(self.heap.len() - 1);
+            return;
+        } else {
+            // replace the key with the smallest count, its count is the possible error
+            let min_slot = self.heap[0];
+            *slot = min_slot;
+            let min = &mut self.slots[min_slot as usize];
+            min.handle = handle;
+            min.error = min.count;
+            min.count += weight;
+            self.sift_down(0);
+            if self.index.len() > self.capacity * REBUILD_FACTOR {
+                self.rebuild_index();
+            }
+            return;
+        }
+        self.sift_down(self.slots[slot_id].heap_pos as usize);
+    }
+
+    /// Returns the count of `el` if it is monitored.
+    pub fn get(&self, el: &str) -> Option<HeavyHitter<'_>> {
+        let slot = *self.index.get(el)? as usize;
+        // the slot of an evicted key monitors another key now
+        self.slots
+            .get(slot)
+            .filter(|slot| self.index.key(slot.handle) == el)
+            .map(|slot| self.to_heavy_hitter(slot))
+    }
+
+    /// Returns the `k` monitored keys with the largest counts, largest first.
+    pub fn top(&self, k: usize) -> alloc::vec::Vec<HeavyHitter<'_>> {
+        let mut slots = self.slots.iter().collect::<alloc::vec::Vec<_>>();
+        slots.sort_by_key(|slot| Reverse(slot.count));
+        slots
+            .into_iter()
+            .take(k)
+            .map(|slot| self.to_heavy_hitter(slot))
+            .collect()
+    }
+
+    /// Returns the monitored keys, in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = HeavyHitter<'_>> {
+        self.slots
+            .iter()
+            .map(move |slot| self.to_heavy_hitter(slot))
+    }
+
+    #[inline]
+    fn to_heavy_hitter(&self, slot: &Slot) -> HeavyHitter<'_> {
+        HeavyHitter {
+            key: self.index.key(slot.handle),
+            count: slot.count,
+            error: slot.error,
+        }
+    }
+
+    /// Replaces the index with one of the monitored keys, which drops the evicted keys.
+    #[cold]
+    fn rebuild_index(&mut self) {
+        let mut index = DenseStringHashMap::with_power_of_two_size(1);
+        for (slot_id, slot) in self.slots.iter_mut().enumerate() {
+            let (handle, _) =
+                index.get_or_create_handle(self.index.key(slot.handle), slot_id as u32);
+            slot.handle = handle;
+        }
+        self.index = index;
+    }
+
+    fn sift_up(&mut self, mut pos: usize) {
+        while pos > 0 {
+            let parent = (pos - 1) / 2;
+            if self.count_at(parent) <= self.count_at(pos) {
+                break;
+            }
+            self.swap(pos, parent);
+            pos = parent;
+        }
+    }
+
+    fn sift_down(&mut self, mut pos: usize) {
+        loop {
+            let mut smallest = pos;
+            for child in [2 * pos + 1, 2 * pos + 2].iter().copied() {
+                if child < self.heap.len() && self.count_at(child) < self.count_at(smallest) {
+                    smallest = child;
+                }
+            }
+            if smallest == pos {
+                break;
+            }
+            self.swap(pos, smallest);
+            pos = smallest;
+        }
+    }
+
+    #[inline]
+    fn count_at(&self, pos: usize) -> u64 {
+        self.slots[self.heap[pos] as usize].count
+    }
+
+    #[inline]
+    fn swap(&mut self, a: usize, b: usize) {
+        self.heap.swap(a, b);
+        self.slots[self.heap[a] as usize].heap_pos = a as u32;
+        self.slots[self.heap[b] as usize].heap_pos = b as u32;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::test_words;
+    use std::vec::Vec;
+
+    #[test]
+    fn top_k_same_as_sort() {
+        let contents = test_words();
+        let mut map = StringHashMap::<u32>::new();
+        for text in contents.split_whitespace() {
+            *map.get_or_create(text, 0) += 1;
+        }
+        let mut sorted = map.iter().collect::<Vec<_>>();
+        sorted.sort_by(|a, b| b.1.cmp(a.1));
+        for &k in [0, 1, 10, 100].iter() {
+            let top = map.top_k_by(k, |count| *count);
+            assert_eq!(top.len(), k);
+            // equal counts may be in different order, compare the counts
+            assert!(top
+                .iter()
+                .map(|entry| entry.1)
+                .eq(sorted[..k].iter().map(|entry| entry.1)));
+            for (key, count) in top {
+                assert_eq!(map.get(key), Some(count));
+            }
+        }
+        assert_eq!(
+            map.top_k_by(map.len() + 10, |count| *count).len(),
+            map.len()
+        );
+        // smallest first
+        assert_eq!(
+            map.top_k_by(1, |count| Reverse(*count))[0].1,
+            sorted.last().unwrap().1
+        );
+    }
+
+    #[test]
+    fn top_k_ties_in_table_order() {
+        let mut map = StringHashMap::<u32>::new();
+        for key in ["a", "b", "c", "d"].iter() {
+            map.get_or_create(*key, 1);
+        }
+        let table_order = map.iter().collect::<Vec<_>>();
+        assert_eq!(map.top_k_by(3, |count| *count), table_order[..3]);
+    }
+
+    #[test]
+    fn heavy_hitters_find_frequent_words() {
+        let contents = test_words();
+        let capacity = 200;
+        let mut heavy_hitters = HeavyHitters::new(capacity);
+        let mut counts = StringHashMap::<u64>::new();
+        for text in contents.split_whitespace() {
+            heavy_hitters.add(text, 1);
+            *counts.get_or_create(text, 0) += 1;
+        }
+        assert_eq!(heavy_hitters.len(), capacity);
+        assert_eq!(heavy_hitters.total(), counts.values().sum::<u64>());
+        // the index is rebuilt, memory is bounded
+        assert!(heavy_hitters.index.len() <= capacity * REBUILD_FACTOR + 1);
+        assert!(heavy_hitters.index.len() < counts.len());
+        let threshold = heavy_hitters.total() / capacity as u64;
+        for (key, count) in counts.iter() {
+            if *count > threshold {
+                let heavy_hitter = heavy_hitters.get(key).unwrap();
+                assert!(heavy_hitter.count >= *count);
+                assert!(heavy_hitter.count - heavy_hitter.error <= *count);
+            }
+        }
+        for heavy_hitter in heavy_hitters.iter() {
+            let count = *counts.get(heavy_hitter.key).unwrap();
+            assert!(heavy_hitter.count - heavy_hitter.error <= count);
+            assert!(count <= heavy_hitter.count);
+        }
+        let top = heavy_hitters.top(10);
+        let exact_top = counts.top_k_by(10, |count| *count);
+        assert_eq!(top[0].key, exact_top[0].0);
+        assert!(top.windows(2).all(|pair| pair[0].count >= pair[1].count));
+    }
+
+    #[test]
+    fn heavy_hitters_evict_smallest() {
+        let mut heavy_hitters = HeavyHitters::new(2);
+        heavy_hitters.add("a", 5);
+        heavy_hitters.add("b", 1);
+        heavy_hitters.add("c", 2);
+        assert_eq!(heavy_hitters.get("b"), None);
+        assert_eq!(
+            heavy_hitters.get("c"),
+            Some(HeavyHitter {
+                key: "c",
+                count: 3,
+                error: 1
+            })
+        );
+        // "b" is added again, it replaces "c" which now has the smallest count
+        heavy_hitters.add("b", 1);
+        assert_eq!(heavy_hitters.get("c"), None);
+        assert_eq!(heavy_hitters.get("b").unwrap().count, 4);
+        assert_eq!(heavy_hitters.top(5)[0].key, "a");
+        assert_eq!(heavy_hitters.top(5).len(), 2);
+    }
+}
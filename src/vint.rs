@@ -0,0 +1,84 @@
+use allocator_api2::alloc::Allocator;
+use allocator_api2::vec::Vec;
+
+/// Maximum number of bytes of an encoded `u32`.
+pub(crate) const MAX_VARINT_LEN: usize = 5;
+
+/// Encodes `value` in the same format as the `vint32` crate, into a vec with any allocator.
+#[inline]
+pub(crate) fn encode_varint_into<A: Allocator>(output: &mut Vec<u8, A>, value: u32) {
+    let (buf, len) = encode_varint(value);
+    output.extend_from_slice(&buf[..len]);
+}
+
+/// Encodes `value` into a buffer, returns the buffer and the number of bytes used.
+#[inline]
+pub(crate) fn encode_varint(mut value: u32) -> ([u8; MAX_VARINT_LEN], usize) {
+    let mut buf = [0u8; MAX_VARINT_LEN];
+    let mut len = 0;
+    while value >= 1 << 7 {
+        buf[len] = ((value & 127) | 128) as u8;
+        value >>= 7;
+        len += 1;
+    }
+    buf[len] = value as u8;
+    (buf, len + 1)
+}
+
+/// Decodes the varint at `pos` and advances `pos` behind it.
+///
+/// Returns `None` if the data ends before the varint, or if it is longer than the varint of a
+/// `u32`.
+#[inline]
+pub(crate) fn decode_varint_slice(data: &[u8], pos: &mut usize) -> Option<u32> {
+    let mut value = 0u32;
+    let mut shift = 0;
+    loop {
+        let byte = *data.get(*pos)?;
+        *pos += 1;
+        value |= u32::from(byte & 127).checked_shl(shift)?;
+        if byte & 128 == 0 {
+            return Some(value);
+        }
+        shift += 7;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use allocator_api2::alloc::Global;
+
+    #[test]
+    fn truncated() {
+        let mut pos = 0;
+        assert_eq!(decode_varint_slice(&[128, 128], &mut pos), None);
+    }
+
+    #[test]
+    fn same_as_vint32() {
+        for value in [
+            0,
+            1,
+            127,
+            128,
+            16_383,
+            16_384,
+            2_097_152,
+            u32::MAX - 1,
+            u32::MAX,
+        ] {
+            let mut expected = std::vec![];
+            vint32::encode_varint_into(&mut expected, value);
+            let mut output = Vec::new_in(Global);
+            encode_varint_into(&mut output, value);
+            assert_eq!(&output[..], &expected[..]);
+
+            let mut pos = 0;
+            assert_eq!(decode_varint_slice(&output, &mut pos), Some(value));
+            assert_eq!(pos, output.len());
+            let mut pos = 0;
+            assert_eq!(vint32::decode_varint_slice(&output, &mut pos), Some(value));
+        }
+    }
+}